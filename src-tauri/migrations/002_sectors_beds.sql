@@ -0,0 +1,7 @@
+-- Campos usados pela distribuição offline para leitos
+ALTER TABLE sectors ADD COLUMN description TEXT;
+ALTER TABLE beds ADD COLUMN token TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_sectors_client ON sectors(client_id);
+CREATE INDEX IF NOT EXISTS idx_beds_client_status ON beds(client_id, status);
+CREATE INDEX IF NOT EXISTS idx_beds_token ON beds(token);
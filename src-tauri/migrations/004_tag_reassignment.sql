@@ -0,0 +1,47 @@
+-- A tag deixa de ser obrigatória: uma peça cuja tag foi movida para outra peça
+-- (troca/liberação de tag) continua no cache, mas "desanexada".
+CREATE TABLE rfid_items_new (
+  id TEXT PRIMARY KEY,
+  tag TEXT UNIQUE,
+  tid TEXT,
+  linen_item_id TEXT,
+  linen_item_name TEXT,
+  linen_item_sku TEXT,
+  full_number TEXT,
+  batch_number INTEGER,
+  piece_number INTEGER,
+  status TEXT,
+  client_id TEXT,
+  client_name TEXT,
+  updated_at INTEGER NOT NULL,
+  synced_at INTEGER,
+  detached_at INTEGER
+);
+
+INSERT INTO rfid_items_new
+  (id, tag, tid, linen_item_id, linen_item_name, linen_item_sku, full_number,
+   batch_number, piece_number, status, client_id, client_name, updated_at, synced_at)
+SELECT id, tag, tid, linen_item_id, linen_item_name, linen_item_sku, full_number,
+       batch_number, piece_number, status, client_id, client_name, updated_at, synced_at
+FROM rfid_items;
+
+DROP TABLE rfid_items;
+ALTER TABLE rfid_items_new RENAME TO rfid_items;
+
+CREATE INDEX IF NOT EXISTS idx_rfid_tag ON rfid_items(tag);
+CREATE INDEX IF NOT EXISTS idx_rfid_tid ON rfid_items(tid);
+CREATE INDEX IF NOT EXISTS idx_rfid_linen_item ON rfid_items(linen_item_id);
+CREATE INDEX IF NOT EXISTS idx_rfid_updated ON rfid_items(updated_at);
+
+-- Registro das movimentações de tag entre peças
+CREATE TABLE IF NOT EXISTS tag_moves (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  tag TEXT NOT NULL,
+  from_item_id TEXT,
+  to_item_id TEXT, -- NULL quando a tag apenas foi liberada
+  moved_at INTEGER NOT NULL, -- updated_at da alteração vencedora
+  recorded_at INTEGER NOT NULL,
+  source TEXT NOT NULL -- 'sync' ou 'local'
+);
+
+CREATE INDEX IF NOT EXISTS idx_tag_moves_tag ON tag_moves(tag);
@@ -0,0 +1,24 @@
+-- Histórico de associações tag <-> peça. Uma linha por período em que a tag
+-- esteve associada à peça; detached_at NULL = associação atual.
+CREATE TABLE IF NOT EXISTS tag_history (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  tag TEXT NOT NULL,
+  tid TEXT,
+  item_id TEXT NOT NULL,
+  associated_at INTEGER NOT NULL,
+  detached_at INTEGER,
+  source TEXT NOT NULL, -- 'sync', 'local' ou 'backfill'
+  detached_source TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_tag_history_tag ON tag_history(tag, associated_at);
+CREATE INDEX IF NOT EXISTS idx_tag_history_item ON tag_history(item_id, associated_at);
+
+-- Associações já presentes no cache viram o ponto de partida do histórico
+INSERT INTO tag_history (tag, tid, item_id, associated_at, source)
+SELECT tag, tid, id, updated_at, 'backfill' FROM rfid_items WHERE tag IS NOT NULL;
+
+-- Movimentações registradas antes do histórico existir
+INSERT INTO tag_history (tag, item_id, associated_at, detached_at, source, detached_source)
+SELECT tag, from_item_id, moved_at, moved_at, 'backfill', source
+FROM tag_moves WHERE from_item_id IS NOT NULL;
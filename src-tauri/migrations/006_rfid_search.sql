@@ -0,0 +1,33 @@
+-- Índice de busca textual sobre o cache RFID (número impresso, SKU, nome, tag).
+-- Tabela FTS5 de conteúdo externo, mantida em sincronia por triggers.
+CREATE VIRTUAL TABLE IF NOT EXISTS rfid_items_fts USING fts5(
+  full_number,
+  linen_item_sku,
+  linen_item_name,
+  tag,
+  content = 'rfid_items',
+  content_rowid = 'rowid',
+  tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER IF NOT EXISTS rfid_items_fts_ai AFTER INSERT ON rfid_items BEGIN
+  INSERT INTO rfid_items_fts (rowid, full_number, linen_item_sku, linen_item_name, tag)
+  VALUES (new.rowid, new.full_number, new.linen_item_sku, new.linen_item_name, new.tag);
+END;
+
+CREATE TRIGGER IF NOT EXISTS rfid_items_fts_ad AFTER DELETE ON rfid_items BEGIN
+  INSERT INTO rfid_items_fts (rfid_items_fts, rowid, full_number, linen_item_sku, linen_item_name, tag)
+  VALUES ('delete', old.rowid, old.full_number, old.linen_item_sku, old.linen_item_name, old.tag);
+END;
+
+CREATE TRIGGER IF NOT EXISTS rfid_items_fts_au AFTER UPDATE ON rfid_items BEGIN
+  INSERT INTO rfid_items_fts (rfid_items_fts, rowid, full_number, linen_item_sku, linen_item_name, tag)
+  VALUES ('delete', old.rowid, old.full_number, old.linen_item_sku, old.linen_item_name, old.tag);
+  INSERT INTO rfid_items_fts (rowid, full_number, linen_item_sku, linen_item_name, tag)
+  VALUES (new.rowid, new.full_number, new.linen_item_sku, new.linen_item_name, new.tag);
+END;
+
+CREATE INDEX IF NOT EXISTS idx_rfid_client_status ON rfid_items(client_id, status);
+
+-- Indexa o que já está no cache
+INSERT INTO rfid_items_fts (rfid_items_fts) VALUES ('rebuild');
@@ -0,0 +1,6 @@
+-- Peças removidas/aposentadas no servidor. Mantidas para que uma atualização
+-- antiga, recebida depois, não ressuscite a peça no cache.
+CREATE TABLE IF NOT EXISTS rfid_tombstones (
+  id TEXT PRIMARY KEY,
+  deleted_at INTEGER NOT NULL
+);
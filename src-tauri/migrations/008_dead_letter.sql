@@ -0,0 +1,35 @@
+-- Histórico de falhas de cada operação (pendente ou morta)
+CREATE TABLE IF NOT EXISTS operation_errors (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  operation_id INTEGER NOT NULL,
+  error TEXT NOT NULL,
+  attempted_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_operation_errors_op ON operation_errors(operation_id, attempted_at);
+
+-- Dead-letter: operações que esgotaram as tentativas. Mantêm o id original
+-- para que o histórico de erros continue ligado a elas.
+CREATE TABLE IF NOT EXISTS failed_operations (
+  id INTEGER PRIMARY KEY,
+  operation_type TEXT NOT NULL,
+  payload TEXT NOT NULL,
+  created_at INTEGER NOT NULL,
+  retry_count INTEGER NOT NULL,
+  last_error TEXT,
+  failed_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_failed_type ON failed_operations(operation_type);
+
+-- Erros já conhecidos das operações pendentes
+INSERT INTO operation_errors (operation_id, error, attempted_at)
+SELECT id, last_error, COALESCE(last_retry_at, created_at)
+FROM pending_operations WHERE last_error IS NOT NULL;
+
+-- Operações que já tinham esgotado as tentativas (antes eram só ignoradas)
+INSERT INTO failed_operations (id, operation_type, payload, created_at, retry_count, last_error, failed_at)
+SELECT id, operation_type, payload, created_at, retry_count, last_error, COALESCE(last_retry_at, created_at)
+FROM pending_operations WHERE retry_count >= 5;
+
+DELETE FROM pending_operations WHERE retry_count >= 5;
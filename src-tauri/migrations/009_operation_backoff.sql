@@ -0,0 +1,10 @@
+-- Agendamento de novas tentativas (backoff exponencial calculado no Rust)
+ALTER TABLE pending_operations ADD COLUMN next_attempt_at INTEGER NOT NULL DEFAULT 0;
+
+UPDATE pending_operations
+SET next_attempt_at = CASE
+  WHEN last_retry_at IS NULL THEN created_at
+  ELSE last_retry_at + 30
+END;
+
+CREATE INDEX IF NOT EXISTS idx_pending_next_attempt ON pending_operations(next_attempt_at, created_at);
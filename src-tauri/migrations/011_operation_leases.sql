@@ -0,0 +1,6 @@
+-- Reserva (lease) de operações em envio: evita que dois loops de sincronização
+-- enviem a mesma operação. Lease vencido devolve a operação à fila.
+ALTER TABLE pending_operations ADD COLUMN lease_token TEXT;
+ALTER TABLE pending_operations ADD COLUMN lease_expires_at INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_pending_lease ON pending_operations(lease_expires_at);
@@ -0,0 +1,4 @@
+-- Versão do esquema do payload de cada operação. Operações gravadas antes da
+-- validação tipada ficam com versão 0 (payload livre, não validado).
+ALTER TABLE pending_operations ADD COLUMN payload_version INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE failed_operations ADD COLUMN payload_version INTEGER NOT NULL DEFAULT 0;
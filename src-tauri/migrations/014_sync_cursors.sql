@@ -0,0 +1,9 @@
+-- Cursores de sincronização emitidos pelo servidor. Sem cursor, o próximo
+-- pull usa o maior updated_at recebido (relógio do servidor), nunca o
+-- relógio local do totem.
+ALTER TABLE sync_log ADD COLUMN cursor TEXT;
+ALTER TABLE sync_log ADD COLUMN high_water_mark INTEGER;
+
+UPDATE sync_log
+SET high_water_mark = (SELECT MAX(updated_at) FROM rfid_items)
+WHERE entity = 'rfid_items';
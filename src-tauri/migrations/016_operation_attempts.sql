@@ -0,0 +1,8 @@
+-- Marca operações que já foram reservadas para envio ao menos uma vez. Depois
+-- disso a operação pode ter chegado ao servidor (ex.: timeout após o envio) e
+-- não recebe mais operações agrupadas.
+ALTER TABLE pending_operations ADD COLUMN attempted INTEGER NOT NULL DEFAULT 0;
+
+UPDATE pending_operations
+SET attempted = 1
+WHERE retry_count > 0 OR lease_token IS NOT NULL;
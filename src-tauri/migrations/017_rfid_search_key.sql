@@ -0,0 +1,74 @@
+-- O índice de busca (006) usava o rowid implícito de rfid_items, cuja chave é
+-- TEXT: esse rowid não é estável (VACUUM pode renumerar) e o índice FTS de
+-- conteúdo externo passaria a apontar para outras peças. A tabela ganha uma
+-- chave INTEGER explícita e o índice é refeito sobre ela.
+DROP TRIGGER IF EXISTS rfid_items_fts_ai;
+DROP TRIGGER IF EXISTS rfid_items_fts_ad;
+DROP TRIGGER IF EXISTS rfid_items_fts_au;
+DROP TABLE IF EXISTS rfid_items_fts;
+
+CREATE TABLE rfid_items_new (
+  seq INTEGER PRIMARY KEY,
+  id TEXT NOT NULL UNIQUE,
+  tag TEXT UNIQUE,
+  tid TEXT,
+  linen_item_id TEXT,
+  linen_item_name TEXT,
+  linen_item_sku TEXT,
+  full_number TEXT,
+  batch_number INTEGER,
+  piece_number INTEGER,
+  status TEXT,
+  client_id TEXT,
+  client_name TEXT,
+  updated_at INTEGER NOT NULL,
+  synced_at INTEGER,
+  detached_at INTEGER
+);
+
+INSERT INTO rfid_items_new
+  (id, tag, tid, linen_item_id, linen_item_name, linen_item_sku, full_number,
+   batch_number, piece_number, status, client_id, client_name, updated_at, synced_at,
+   detached_at)
+SELECT id, tag, tid, linen_item_id, linen_item_name, linen_item_sku, full_number,
+       batch_number, piece_number, status, client_id, client_name, updated_at, synced_at,
+       detached_at
+FROM rfid_items;
+
+DROP TABLE rfid_items;
+ALTER TABLE rfid_items_new RENAME TO rfid_items;
+
+CREATE INDEX IF NOT EXISTS idx_rfid_tag ON rfid_items(tag);
+CREATE INDEX IF NOT EXISTS idx_rfid_tid ON rfid_items(tid);
+CREATE INDEX IF NOT EXISTS idx_rfid_linen_item ON rfid_items(linen_item_id);
+CREATE INDEX IF NOT EXISTS idx_rfid_updated ON rfid_items(updated_at);
+CREATE INDEX IF NOT EXISTS idx_rfid_client_status ON rfid_items(client_id, status);
+
+CREATE VIRTUAL TABLE rfid_items_fts USING fts5(
+  full_number,
+  linen_item_sku,
+  linen_item_name,
+  tag,
+  content = 'rfid_items',
+  content_rowid = 'seq',
+  tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER rfid_items_fts_ai AFTER INSERT ON rfid_items BEGIN
+  INSERT INTO rfid_items_fts (rowid, full_number, linen_item_sku, linen_item_name, tag)
+  VALUES (new.seq, new.full_number, new.linen_item_sku, new.linen_item_name, new.tag);
+END;
+
+CREATE TRIGGER rfid_items_fts_ad AFTER DELETE ON rfid_items BEGIN
+  INSERT INTO rfid_items_fts (rfid_items_fts, rowid, full_number, linen_item_sku, linen_item_name, tag)
+  VALUES ('delete', old.seq, old.full_number, old.linen_item_sku, old.linen_item_name, old.tag);
+END;
+
+CREATE TRIGGER rfid_items_fts_au AFTER UPDATE ON rfid_items BEGIN
+  INSERT INTO rfid_items_fts (rfid_items_fts, rowid, full_number, linen_item_sku, linen_item_name, tag)
+  VALUES ('delete', old.seq, old.full_number, old.linen_item_sku, old.linen_item_name, old.tag);
+  INSERT INTO rfid_items_fts (rowid, full_number, linen_item_sku, linen_item_name, tag)
+  VALUES (new.seq, new.full_number, new.linen_item_sku, new.linen_item_name, new.tag);
+END;
+
+INSERT INTO rfid_items_fts (rfid_items_fts) VALUES ('rebuild');
@@ -0,0 +1,112 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// Política de nova tentativa para operações da fila offline: espera exponencial
+// a partir de `base_secs`, limitada a `max_secs`, com variação aleatória de
+// ±`jitter` (fração) para que vários totens não batam no servidor ao mesmo tempo.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackoffPolicy {
+    pub base_secs: u64,
+    pub max_secs: u64,
+    pub multiplier: f64,
+    pub jitter: f64,
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        BackoffPolicy {
+            base_secs: 30,
+            max_secs: 3600,
+            multiplier: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl BackoffPolicy {
+    // Espera sem jitter após a `retry_count`-ésima falha (1 = primeira falha)
+    pub fn base_delay_secs(&self, retry_count: i32) -> f64 {
+        let exponent = retry_count.saturating_sub(1).max(0);
+        let delay = self.base_secs as f64 * self.multiplier.max(1.0).powi(exponent);
+        delay.min(self.max_secs as f64)
+    }
+
+    pub fn delay_secs<R: Rng>(&self, retry_count: i32, rng: &mut R) -> i64 {
+        let delay = self.base_delay_secs(retry_count);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = if jitter > 0.0 {
+            rng.gen_range((1.0 - jitter)..=(1.0 + jitter))
+        } else {
+            1.0
+        };
+        // O teto vale também para o jitter: max_secs é a maior espera possível
+        (delay * factor).min(self.max_secs as f64).round().max(1.0) as i64
+    }
+
+    pub fn next_attempt_at(&self, now: i64, retry_count: i32) -> i64 {
+        now + self.delay_secs(retry_count, &mut rand::thread_rng())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn without_jitter() -> BackoffPolicy {
+        BackoffPolicy {
+            jitter: 0.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn grows_exponentially_from_base() {
+        let policy = without_jitter();
+        let mut rng = StdRng::seed_from_u64(1);
+        let delays: Vec<i64> = (0..=6).map(|retry| policy.delay_secs(retry, &mut rng)).collect();
+        assert_eq!(delays, vec![30, 30, 60, 120, 240, 480, 960]);
+
+        let triple = BackoffPolicy {
+            multiplier: 3.0,
+            ..without_jitter()
+        };
+        assert_eq!(triple.base_delay_secs(3), 270.0);
+    }
+
+    #[test]
+    fn never_waits_longer_than_max() {
+        let policy = BackoffPolicy::default();
+        assert_eq!(policy.base_delay_secs(7), 1920.0);
+        assert_eq!(policy.base_delay_secs(8), 3600.0);
+        assert_eq!(policy.base_delay_secs(i32::MAX), 3600.0);
+
+        // Já no teto, o jitter só pode encurtar a espera
+        let mut rng = StdRng::seed_from_u64(7);
+        let delays: Vec<i64> = (0..500).map(|_| policy.delay_secs(20, &mut rng)).collect();
+        assert!(delays.iter().all(|&d| (2880..=3600).contains(&d)), "{:?}", delays);
+        assert!(delays.contains(&3600));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let policy = BackoffPolicy::default();
+        let mut rng = StdRng::seed_from_u64(42);
+        let delays: Vec<i64> = (0..1000).map(|_| policy.delay_secs(3, &mut rng)).collect();
+
+        // 120 s ± 20%
+        assert!(delays.iter().all(|&d| (96..=144).contains(&d)), "{:?}", delays);
+        assert!(delays.iter().any(|&d| d < 105));
+        assert!(delays.iter().any(|&d| d > 135));
+
+        // Jitter acima de 100% é limitado e a espera nunca chega a zero
+        let wild = BackoffPolicy {
+            base_secs: 1,
+            jitter: 5.0,
+            ..Default::default()
+        };
+        assert!((0..1000).all(|_| (1..=2).contains(&wild.delay_secs(1, &mut rng))));
+    }
+}
@@ -1,6 +1,31 @@
-use tauri::State;
-use crate::db::{Database, RfidItem};
+use tauri::{AppHandle, Manager, State};
+use crate::backoff::BackoffPolicy;
+use crate::db::{
+    Bed, BedLocation, BulkLookupResult, ConfigEntry, Database, FailedOperation,
+    FailedOperationDetail, LinenItem, QueuedOperation, RfidItem, RfidSearchQuery, RfidSearchResult,
+    RfidSyncBatch, RfidSyncSummary, Sector, SectorWithBeds, TagHistoryEntry,
+};
+use crate::operation::Operation;
+use crate::sync::{SyncConfig, SyncController, SyncStatus};
+use serde::Serialize;
 use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+
+#[derive(Serialize, Clone)]
+struct SettingChanged {
+    key: String,
+    value: Option<JsonValue>,
+    updated_at: i64,
+}
+
+fn emit_setting_changed(app: &AppHandle, key: &str, value: Option<JsonValue>, updated_at: i64) {
+    let payload = SettingChanged {
+        key: key.to_string(),
+        value,
+        updated_at,
+    };
+    let _ = app.emit_all("settings-changed", payload);
+}
 
 #[tauri::command]
 pub fn lookup_rfid_local(tag: String, db: State<Database>) -> Result<Option<RfidItem>, String> {
@@ -8,6 +33,12 @@ pub fn lookup_rfid_local(tag: String, db: State<Database>) -> Result<Option<Rfid
         .map_err(|e| format!("Erro ao buscar tag no cache local: {}", e))
 }
 
+#[tauri::command]
+pub fn lookup_rfid_items_bulk(tags: Vec<String>, db: State<Database>) -> Result<BulkLookupResult, String> {
+    db.lookup_rfid_items_bulk(&tags)
+        .map_err(|e| format!("Erro ao buscar tags em lote no cache local: {}", e))
+}
+
 #[tauri::command]
 pub fn cache_rfid_item(item: RfidItem, db: State<Database>) -> Result<(), String> {
     db.upsert_rfid_item(&item)
@@ -15,18 +46,140 @@ pub fn cache_rfid_item(item: RfidItem, db: State<Database>) -> Result<(), String
 }
 
 #[tauri::command]
-pub fn bulk_cache_rfid_items(items: Vec<RfidItem>, db: State<Database>) -> Result<usize, String> {
+pub fn bulk_cache_rfid_items(
+    items: Vec<RfidItem>,
+    db: State<Database>,
+) -> Result<RfidSyncSummary, String> {
     db.bulk_upsert_rfid_items(&items)
         .map_err(|e| format!("Erro ao salvar itens em lote: {}", e))
 }
 
+#[tauri::command]
+pub fn apply_rfid_sync(batch: RfidSyncBatch, db: State<Database>) -> Result<RfidSyncSummary, String> {
+    db.apply_rfid_sync(&batch)
+        .map_err(|e| format!("Erro ao aplicar sincronização de itens: {}", e))
+}
+
+#[tauri::command]
+pub fn detach_rfid_tag_local(tag: String, at: i64, db: State<Database>) -> Result<Option<String>, String> {
+    db.detach_rfid_tag(&tag, at)
+        .map_err(|e| format!("Erro ao liberar tag no cache local: {}", e))
+}
+
+#[tauri::command]
+pub fn replace_rfid_tag_local(
+    item_id: String,
+    new_tag: String,
+    new_tid: Option<String>,
+    at: i64,
+    db: State<Database>,
+) -> Result<bool, String> {
+    db.replace_rfid_tag(&item_id, &new_tag, new_tid.as_deref(), at)
+        .map_err(|e| format!("Erro ao trocar tag no cache local: {}", e))
+}
+
+#[tauri::command]
+pub fn get_tag_history(
+    tag: Option<String>,
+    item_id: Option<String>,
+    db: State<Database>,
+) -> Result<Vec<TagHistoryEntry>, String> {
+    let result = match (tag, item_id) {
+        (Some(tag), _) => db.get_tag_history(&tag),
+        (None, Some(item_id)) => db.get_item_tag_history(&item_id),
+        (None, None) => return Err("Informe a tag ou a peça".to_string()),
+    };
+    result.map_err(|e| format!("Erro ao buscar histórico da tag: {}", e))
+}
+
+#[tauri::command]
+pub fn search_rfid_local(query: RfidSearchQuery, db: State<Database>) -> Result<RfidSearchResult, String> {
+    db.search_rfid_items(&query)
+        .map_err(|e| format!("Erro ao pesquisar peças no cache local: {}", e))
+}
+
+#[tauri::command]
+pub fn cache_linen_item(item: LinenItem, db: State<Database>) -> Result<(), String> {
+    db.upsert_linen_item(&item)
+        .map_err(|e| format!("Erro ao salvar produto no cache: {}", e))
+}
+
+#[tauri::command]
+pub fn bulk_cache_linen_items(items: Vec<LinenItem>, db: State<Database>) -> Result<usize, String> {
+    db.bulk_upsert_linen_items(&items)
+        .map_err(|e| format!("Erro ao salvar produtos em lote: {}", e))
+}
+
+#[tauri::command]
+pub fn list_linen_items_local(client_id: String, db: State<Database>) -> Result<Vec<LinenItem>, String> {
+    db.list_linen_items_by_client(&client_id)
+        .map_err(|e| format!("Erro ao listar produtos do cache local: {}", e))
+}
+
+#[tauri::command]
+pub fn get_linen_item_local(id: String, db: State<Database>) -> Result<Option<LinenItem>, String> {
+    db.get_linen_item(&id)
+        .map_err(|e| format!("Erro ao buscar produto no cache local: {}", e))
+}
+
+#[tauri::command]
+pub fn bulk_cache_sectors(sectors: Vec<Sector>, db: State<Database>) -> Result<usize, String> {
+    db.bulk_upsert_sectors(&sectors)
+        .map_err(|e| format!("Erro ao salvar setores em lote: {}", e))
+}
+
+#[tauri::command]
+pub fn bulk_cache_beds(beds: Vec<Bed>, db: State<Database>) -> Result<usize, String> {
+    db.bulk_upsert_beds(&beds)
+        .map_err(|e| format!("Erro ao salvar leitos em lote: {}", e))
+}
+
+#[tauri::command]
+pub fn list_sectors_local(client_id: String, db: State<Database>) -> Result<Vec<Sector>, String> {
+    db.list_sectors_by_client(&client_id)
+        .map_err(|e| format!("Erro ao listar setores do cache local: {}", e))
+}
+
+#[tauri::command]
+pub fn list_beds_local(
+    client_id: String,
+    sector_id: Option<String>,
+    status: Option<String>,
+    db: State<Database>,
+) -> Result<Vec<Bed>, String> {
+    db.list_beds(&client_id, sector_id.as_deref(), status.as_deref())
+        .map_err(|e| format!("Erro ao listar leitos do cache local: {}", e))
+}
+
+#[tauri::command]
+pub fn get_bed_by_token_local(token: String, db: State<Database>) -> Result<Option<BedLocation>, String> {
+    db.get_bed_by_token(&token)
+        .map_err(|e| format!("Erro ao buscar leito pelo token: {}", e))
+}
+
+#[tauri::command]
+pub fn get_sector_tree_local(
+    client_id: String,
+    status: Option<String>,
+    db: State<Database>,
+) -> Result<Vec<SectorWithBeds>, String> {
+    db.get_sector_tree(&client_id, status.as_deref())
+        .map_err(|e| format!("Erro ao montar setores e leitos do cache local: {}", e))
+}
+
 #[tauri::command]
 pub fn queue_operation(
     operation_type: String,
     payload: String,
     db: State<Database>,
-) -> Result<i64, String> {
-    db.queue_operation(&operation_type, &payload)
+) -> Result<QueuedOperation, String> {
+    enqueue(&db, &operation_type, &payload)
+}
+
+// Payload inválido é recusado aqui, antes de entrar na fila
+fn enqueue(db: &Database, operation_type: &str, payload: &str) -> Result<QueuedOperation, String> {
+    let operation = Operation::parse(operation_type, payload).map_err(|e| e.to_string())?;
+    db.queue_operation(&operation)
         .map_err(|e| format!("Erro ao enfileirar operação: {}", e))
 }
 
@@ -41,13 +194,105 @@ pub fn get_pending_operations(
 }
 
 #[tauri::command]
-pub fn delete_operation(id: i64, db: State<Database>) -> Result<(), String> {
+pub fn get_due_operations(
+    limit: Option<usize>,
+    now: Option<i64>,
+    db: State<Database>,
+) -> Result<Vec<crate::db::PendingOperation>, String> {
+    let now = now.unwrap_or_else(|| chrono::Utc::now().timestamp());
+    db.get_due_operations(limit.unwrap_or(100), now)
+        .map_err(|e| format!("Erro ao buscar operações prontas para envio: {}", e))
+}
+
+#[tauri::command]
+pub fn get_backoff_policy(db: State<Database>) -> Result<BackoffPolicy, String> {
+    db.get_backoff_policy()
+        .map_err(|e| format!("Erro ao buscar política de novas tentativas: {}", e))
+}
+
+#[tauri::command]
+pub fn set_backoff_policy(policy: BackoffPolicy, db: State<Database>) -> Result<(), String> {
+    db.set_backoff_policy(&policy)
+        .map_err(|e| format!("Erro ao salvar política de novas tentativas: {}", e))
+}
+
+// Sem config, reutiliza a última gravada (URL/chave enviadas pela UI)
+#[tauri::command]
+pub fn start_sync(
+    config: Option<SyncConfig>,
+    app: AppHandle,
+    db: State<Database>,
+    controller: State<SyncController>,
+) -> Result<(), String> {
+    let config = match config {
+        Some(config) => {
+            db.set_sync_config(&config)
+                .map_err(|e| format!("Erro ao salvar configuração de sincronização: {}", e))?;
+            config
+        }
+        None => db
+            .get_sync_config()
+            .map_err(|e| format!("Erro ao buscar configuração de sincronização: {}", e))?,
+    };
+    controller.start(&app, db.inner().clone(), config)
+}
+
+#[tauri::command]
+pub fn stop_sync(controller: State<SyncController>) {
+    controller.stop();
+}
+
+#[tauri::command]
+pub fn force_sync(controller: State<SyncController>) -> Result<(), String> {
+    controller.force_sync()
+}
+
+#[tauri::command]
+pub fn get_sync_status(controller: State<SyncController>) -> SyncStatus {
+    controller.status()
+}
+
+#[tauri::command]
+pub fn claim_operations(
+    limit: Option<usize>,
+    lease_secs: Option<i64>,
+    db: State<Database>,
+) -> Result<Vec<crate::db::PendingOperation>, String> {
+    db.claim_operations(limit.unwrap_or(50), lease_secs.unwrap_or(120))
+        .map_err(|e| format!("Erro ao reservar operações para envio: {}", e))
+}
+
+#[tauri::command]
+pub fn complete_operation(id: i64, lease_token: String, db: State<Database>) -> Result<bool, String> {
+    db.complete_operation(id, &lease_token)
+        .map_err(|e| format!("Erro ao concluir operação: {}", e))
+}
+
+#[tauri::command]
+pub fn fail_operation(
+    id: i64,
+    lease_token: String,
+    error: String,
+    db: State<Database>,
+) -> Result<bool, String> {
+    db.fail_operation(id, &lease_token, &error)
+        .map_err(|e| format!("Erro ao registrar falha da operação: {}", e))
+}
+
+#[tauri::command]
+pub fn release_operations(lease_token: String, db: State<Database>) -> Result<usize, String> {
+    db.release_operations(&lease_token)
+        .map_err(|e| format!("Erro ao liberar operações reservadas: {}", e))
+}
+
+#[tauri::command]
+pub fn delete_operation(id: i64, db: State<Database>) -> Result<bool, String> {
     db.delete_operation(id)
         .map_err(|e| format!("Erro ao deletar operação: {}", e))
 }
 
 #[tauri::command]
-pub fn increment_retry_count(id: i64, error: String, db: State<Database>) -> Result<(), String> {
+pub fn increment_retry_count(id: i64, error: String, db: State<Database>) -> Result<bool, String> {
     db.increment_retry_count(id, &error)
         .map_err(|e| format!("Erro ao incrementar contador de retry: {}", e))
 }
@@ -58,6 +303,40 @@ pub fn get_pending_count(db: State<Database>) -> Result<i64, String> {
         .map_err(|e| format!("Erro ao contar operações pendentes: {}", e))
 }
 
+#[tauri::command]
+pub fn list_failed_operations(
+    limit: Option<usize>,
+    db: State<Database>,
+) -> Result<Vec<FailedOperation>, String> {
+    db.list_failed_operations(limit.unwrap_or(100))
+        .map_err(|e| format!("Erro ao listar operações com falha: {}", e))
+}
+
+#[tauri::command]
+pub fn get_failed_operation(
+    id: i64,
+    db: State<Database>,
+) -> Result<Option<FailedOperationDetail>, String> {
+    db.get_failed_operation(id)
+        .map_err(|e| format!("Erro ao buscar operação com falha: {}", e))
+}
+
+#[tauri::command]
+pub fn requeue_failed_operation(
+    id: i64,
+    payload: Option<String>,
+    db: State<Database>,
+) -> Result<bool, String> {
+    db.requeue_failed_operation(id, payload.as_deref())
+        .map_err(|e| format!("Erro ao reenfileirar operação: {}", e))
+}
+
+#[tauri::command]
+pub fn discard_failed_operation(id: i64, db: State<Database>) -> Result<bool, String> {
+    db.discard_failed_operation(id)
+        .map_err(|e| format!("Erro ao descartar operação: {}", e))
+}
+
 #[tauri::command]
 pub fn get_last_sync(entity: String, db: State<Database>) -> Result<i64, String> {
     db.get_last_sync(&entity)
@@ -70,9 +349,112 @@ pub fn update_sync_log(entity: String, count: i64, db: State<Database>) -> Resul
         .map_err(|e| format!("Erro ao atualizar log de sincronização: {}", e))
 }
 
+// Chaves internas (marcadores, config do sync com api_key) não passam pelos
+// comandos de configuração da webview
+fn check_setting_key(key: &str) -> Result<(), String> {
+    if Database::is_internal_config_key(key) {
+        return Err(format!("Chave de configuração reservada: {}", key));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_setting(key: String, db: State<Database>) -> Result<Option<ConfigEntry>, String> {
+    check_setting_key(&key)?;
+    db.get_config(&key)
+        .map_err(|e| format!("Erro ao buscar configuração: {}", e))
+}
+
+#[tauri::command]
+pub fn set_setting(
+    key: String,
+    value: JsonValue,
+    app: AppHandle,
+    db: State<Database>,
+) -> Result<ConfigEntry, String> {
+    check_setting_key(&key)?;
+    let entry = db
+        .set_config(&key, &value)
+        .map_err(|e| format!("Erro ao salvar configuração: {}", e))?;
+    emit_setting_changed(&app, &entry.key, Some(entry.value.clone()), entry.updated_at);
+    Ok(entry)
+}
+
+#[tauri::command]
+pub fn delete_setting(key: String, app: AppHandle, db: State<Database>) -> Result<bool, String> {
+    check_setting_key(&key)?;
+    let deleted = db
+        .delete_config(&key)
+        .map_err(|e| format!("Erro ao remover configuração: {}", e))?;
+    if deleted {
+        emit_setting_changed(&app, &key, None, chrono::Utc::now().timestamp());
+    }
+    Ok(deleted)
+}
+
+#[tauri::command]
+pub fn list_settings(db: State<Database>) -> Result<Vec<ConfigEntry>, String> {
+    db.list_config()
+        .map_err(|e| format!("Erro ao listar configurações: {}", e))
+}
+
+#[tauri::command]
+pub fn import_settings(
+    entries: HashMap<String, JsonValue>,
+    app: AppHandle,
+    db: State<Database>,
+) -> Result<usize, String> {
+    let entries: Vec<(String, JsonValue)> = entries.into_iter().collect();
+    let imported = db
+        .import_config(&entries)
+        .map_err(|e| format!("Erro ao importar configurações: {}", e))?;
+    for entry in &imported {
+        emit_setting_changed(&app, &entry.key, Some(entry.value.clone()), entry.updated_at);
+    }
+    Ok(imported.len())
+}
+
 #[tauri::command]
 pub fn get_db_stats(db: State<Database>) -> Result<JsonValue, String> {
     db.get_stats()
         .map_err(|e| format!("Erro ao buscar estatísticas: {}", e))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TempDb;
+
+    #[test]
+    fn queue_operation_rejects_invalid_payload_without_queueing() {
+        let db = TempDb::new("enqueue");
+        let rejected = [
+            ("teleport", r#"{"tags":["E200AA01"]}"#),
+            ("distribute", r#"{"tags":["E200AA01"],"bedId":"#),
+            ("distribute", r#"{"tags":["E200AA01"],"bedID":"b1"}"#),
+            ("distribute", r#"{"tags":["E200ZZ01"],"bedId":"b1"}"#),
+            ("weighing", r#"{"control_id":"c1","peso_total":0}"#),
+        ];
+        for (kind, payload) in rejected {
+            let error = enqueue(&db, kind, payload).unwrap_err();
+            assert!(!error.starts_with("Erro ao enfileirar"), "{}: {}", kind, error);
+        }
+        assert_eq!(db.get_pending_count().unwrap(), 0);
+
+        let queued = enqueue(&db, "distribute", r#"{"tags":["e2-00-aa-01"],"bedId":"b1"}"#).unwrap();
+        let pending = db.get_pending_operations(10).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, queued.id);
+        assert_eq!(pending[0].payload, r#"{"bedId":"b1","tags":["E200AA01"]}"#);
+    }
+
+    #[test]
+    fn settings_commands_refuse_internal_keys() {
+        for key in ["_meta:local_storage_imported", "sync:config", "sync:backoff"] {
+            let error = check_setting_key(key).unwrap_err();
+            assert!(error.contains("reservada"), "{}: {}", key, error);
+        }
+        check_setting_key("myecolav:settings:v1").unwrap();
+        check_setting_key("selectedClient").unwrap();
+    }
+}
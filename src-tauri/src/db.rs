@@ -1,8 +1,16 @@
 use rusqlite::{Connection, Result as SqlResult, params};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::path::PathBuf;
 
+use crate::backoff::BackoffPolicy;
+use crate::sync::SyncConfig;
+use crate::operation::{Coalesce, Operation, PAYLOAD_SCHEMA_VERSION, TERMINAL_STAGE};
+use crate::tag::{self, TagError};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RfidItem {
     pub id: String,
@@ -18,6 +26,152 @@ pub struct RfidItem {
     pub client_id: Option<String>,
     pub client_name: Option<String>,
     pub updated_at: i64,
+    // Status projetado por operações ainda na fila (não vem do servidor)
+    #[serde(default)]
+    pub pending_sync: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RfidTombstone {
+    pub id: String,
+    pub deleted_at: i64,
+}
+
+// Lote da sincronização incremental: itens novos/alterados e remoções, mais
+// o cursor opaco que o servidor quer receber no próximo pull (se houver)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RfidSyncBatch {
+    #[serde(default)]
+    pub items: Vec<RfidItem>,
+    #[serde(default)]
+    pub deleted: Vec<RfidTombstone>,
+    #[serde(default, alias = "next_cursor")]
+    pub cursor: Option<String>,
+}
+
+// Posição da sincronização de uma entidade. last_sync_at é só informativo
+// (relógio local); o pull usa o cursor ou, na falta dele, o high_water_mark.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncCursor {
+    pub cursor: Option<String>,
+    pub high_water_mark: Option<i64>,
+    pub last_sync_at: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RfidSyncSummary {
+    pub upserted: usize,
+    pub deleted: usize,
+    // Linhas ignoradas por dados inválidos (ex.: tag fora do formato)
+    pub rejected: Vec<RejectedRfidItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RejectedRfidItem {
+    pub id: String,
+    pub tag: String,
+    pub error: String,
+}
+
+// Troca local recusada: a nova tag está com outra peça alterada depois
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagConflict {
+    pub tag: String,
+    pub owner_id: String,
+}
+
+impl std::fmt::Display for TagConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Tag '{}' pertence à peça '{}', alterada depois desta troca",
+            self.tag, self.owner_id
+        )
+    }
+}
+
+impl std::error::Error for TagConflict {}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkLookupResult {
+    // Itens encontrados, indexados pela tag exatamente como foi enviada
+    pub found: HashMap<String, RfidItem>,
+    pub unknown: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RfidSearchQuery {
+    pub text: String,
+    pub client_id: Option<String>,
+    pub status: Option<String>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RfidSearchResult {
+    pub items: Vec<RfidItem>,
+    pub total: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagHistoryEntry {
+    pub id: i64,
+    pub tag: String,
+    pub tid: Option<String>,
+    pub item_id: String,
+    pub associated_at: i64,
+    pub detached_at: Option<i64>,
+    pub source: String,
+    pub detached_source: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinenItem {
+    pub id: String,
+    pub name: String,
+    pub sku: Option<String>,
+    pub client_id: Option<String>,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sector {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub client_id: Option<String>,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bed {
+    pub id: String,
+    pub number: String,
+    pub sector_id: Option<String>,
+    pub status: Option<String>,
+    pub client_id: Option<String>,
+    pub token: Option<String>,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BedLocation {
+    pub bed: Bed,
+    pub sector: Option<Sector>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SectorWithBeds {
+    pub sector: Sector,
+    pub beds: Vec<Bed>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: JsonValue,
+    pub updated_at: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,9 +179,95 @@ pub struct PendingOperation {
     pub id: i64,
     pub operation_type: String,
     pub payload: String,
+    pub payload_version: i32,
+    pub created_at: i64,
+    pub retry_count: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: i64,
+    pub idempotency_key: String,
+    pub lease_token: Option<String>,
+    pub lease_expires_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueOutcome {
+    Queued,
+    // Unida a uma operação equivalente ainda não enviada
+    Merged,
+    // Duplicata de uma operação ainda não enviada
+    Dropped,
+}
+
+// Em Merged/Dropped, id e chave são os da operação que já estava na fila.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedOperation {
+    pub id: i64,
+    pub idempotency_key: String,
+    pub outcome: QueueOutcome,
+}
+
+// Uma migration é um script SQL em migrations/ ou, quando precisa de lógica que
+// não cabe em SQL (ex.: normalização de tags), uma função Rust.
+enum Migration {
+    Sql(&'static str),
+    Rust(fn(&rusqlite::Transaction) -> SqlResult<()>),
+}
+
+// Migrations numeradas, compiladas no binário. Cada uma é aplicada uma única vez,
+// em ordem, dentro de uma transação. Novas migrations entram sempre no final.
+const MIGRATIONS: &[(i64, Migration)] = &[
+    (1, Migration::Sql(include_str!("../migrations/001_initial.sql"))),
+    (2, Migration::Sql(include_str!("../migrations/002_sectors_beds.sql"))),
+    (3, Migration::Rust(Database::canonicalize_rfid_tags)),
+    (4, Migration::Sql(include_str!("../migrations/004_tag_reassignment.sql"))),
+    (5, Migration::Sql(include_str!("../migrations/005_tag_history.sql"))),
+    (6, Migration::Sql(include_str!("../migrations/006_rfid_search.sql"))),
+    (7, Migration::Sql(include_str!("../migrations/007_rfid_tombstones.sql"))),
+    (8, Migration::Sql(include_str!("../migrations/008_dead_letter.sql"))),
+    (9, Migration::Sql(include_str!("../migrations/009_operation_backoff.sql"))),
+    (10, Migration::Rust(Database::add_idempotency_keys)),
+    (11, Migration::Sql(include_str!("../migrations/011_operation_leases.sql"))),
+    (12, Migration::Sql(include_str!("../migrations/012_operation_payload_version.sql"))),
+    (13, Migration::Rust(Database::add_operation_dependencies)),
+    (14, Migration::Sql(include_str!("../migrations/014_sync_cursors.sql"))),
+    (15, Migration::Rust(Database::add_rfid_item_overlay)),
+    (16, Migration::Sql(include_str!("../migrations/016_operation_attempts.sql"))),
+    (17, Migration::Sql(include_str!("../migrations/017_rfid_search_key.sql"))),
+];
+
+pub const SCHEMA_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].0;
+
+// Tentativas antes de a operação ir para a dead-letter (failed_operations)
+pub const MAX_OPERATION_RETRIES: i32 = 5;
+
+// Chave em local_config com a política de backoff da fila (JSON de BackoffPolicy)
+pub const BACKOFF_POLICY_KEY: &str = "sync:backoff";
+pub const SYNC_CONFIG_KEY: &str = "sync:config";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedOperation {
+    pub id: i64,
+    pub operation_type: String,
+    pub payload: String,
+    pub payload_version: i32,
     pub created_at: i64,
     pub retry_count: i32,
     pub last_error: Option<String>,
+    pub failed_at: i64,
+    pub idempotency_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationError {
+    pub error: String,
+    pub attempted_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedOperationDetail {
+    pub operation: FailedOperation,
+    pub errors: Vec<OperationError>,
 }
 
 #[derive(Clone)]
@@ -37,70 +277,550 @@ pub struct Database {
 
 impl Database {
     pub fn new(db_path: PathBuf) -> SqlResult<Self> {
-        let conn = Connection::open(db_path)?;
+        let mut conn = Connection::open(db_path)?;
         
         // Executar migrations
-        Self::run_migrations(&conn)?;
+        Self::run_migrations(&mut conn)?;
         
         Ok(Database {
             conn: Arc::new(Mutex::new(conn)),
         })
     }
 
-    fn run_migrations(conn: &Connection) -> SqlResult<()> {
-        let migration_sql = include_str!("../migrations/001_initial.sql");
-        conn.execute_batch(migration_sql)?;
+    fn run_migrations(conn: &mut Connection) -> SqlResult<()> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS schema_version (
+               version INTEGER PRIMARY KEY,
+               applied_at INTEGER NOT NULL
+             );",
+        )?;
+
+        let current = Self::schema_version(conn)?;
+        if current > SCHEMA_VERSION {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ERROR),
+                Some(format!(
+                    "Banco de dados na versão {} é mais novo que este aplicativo (suporta até {})",
+                    current, SCHEMA_VERSION
+                )),
+            ));
+        }
+
+        for (version, migration) in MIGRATIONS.iter().filter(|(v, _)| *v > current) {
+            let tx = conn.transaction()?;
+            match migration {
+                Migration::Sql(sql) => tx.execute_batch(sql)?,
+                Migration::Rust(apply) => apply(&tx)?,
+            }
+            tx.execute(
+                "INSERT INTO schema_version (version, applied_at) VALUES (?1, ?2)",
+                params![version, chrono::Utc::now().timestamp()],
+            )?;
+            tx.commit()?;
+        }
+
+        Ok(())
+    }
+
+    // Migration 3: reescreve tag/tid já cacheados na forma canônica. Quando duas
+    // linhas colidem na mesma tag canônica, fica a mais recente. Valores que não
+    // são hexadecimais válidos ficam como estão (nunca casam em uma busca).
+    fn canonicalize_rfid_tags(tx: &rusqlite::Transaction) -> SqlResult<()> {
+        let rows: Vec<(String, String, Option<String>)> = {
+            let mut stmt = tx.prepare(
+                "SELECT id, tag, tid FROM rfid_items ORDER BY updated_at DESC, id DESC"
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            rows.collect::<SqlResult<_>>()?
+        };
+
+        let mut seen = std::collections::HashSet::new();
+        let mut updates = Vec::new();
+        for (id, tag, tid) in rows {
+            let canonical_tag = tag::normalize_tag(&tag).unwrap_or_else(|_| tag.clone());
+            if !seen.insert(canonical_tag.clone()) {
+                tx.execute("DELETE FROM rfid_items WHERE id = ?1", params![id])?;
+                continue;
+            }
+            let canonical_tid = match tag::normalize_optional_tag(tid.as_deref()) {
+                Ok(value) => value,
+                Err(_) => tid.clone(),
+            };
+            if canonical_tag != tag || canonical_tid != tid {
+                updates.push((id, canonical_tag, canonical_tid));
+            }
+        }
+
+        let mut stmt = tx.prepare("UPDATE rfid_items SET tag = ?2, tid = ?3 WHERE id = ?1")?;
+        for (id, tag, tid) in updates {
+            stmt.execute(params![id, tag, tid])?;
+        }
+
+        Ok(())
+    }
+
+    // Migration 10: chave de idempotência por operação. As operações já
+    // enfileiradas recebem uma chave nova, que passa a valer para sempre.
+    fn add_idempotency_keys(tx: &rusqlite::Transaction) -> SqlResult<()> {
+        tx.execute_batch(
+            "ALTER TABLE pending_operations ADD COLUMN idempotency_key TEXT;
+             ALTER TABLE failed_operations ADD COLUMN idempotency_key TEXT;",
+        )?;
+
+        for table in ["pending_operations", "failed_operations"] {
+            let ids: Vec<i64> = {
+                let mut stmt = tx.prepare(&format!("SELECT id FROM {}", table))?;
+                let ids = stmt.query_map([], |row| row.get(0))?;
+                ids.collect::<SqlResult<_>>()?
+            };
+            let mut stmt =
+                tx.prepare(&format!("UPDATE {} SET idempotency_key = ?2 WHERE id = ?1", table))?;
+            for id in ids {
+                stmt.execute(params![id, Self::new_idempotency_key()])?;
+            }
+        }
+
+        tx.execute_batch(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_pending_idempotency
+               ON pending_operations(idempotency_key);",
+        )?;
+        Ok(())
+    }
+
+    // Migration 13: dependências por tag/peça entre operações da fila. As
+    // operações já enfileiradas (inclusive na dead-letter) entram em ordem de id.
+    fn add_operation_dependencies(tx: &rusqlite::Transaction) -> SqlResult<()> {
+        tx.execute_batch(
+            "CREATE TABLE IF NOT EXISTS operation_dependencies (
+                operation_id INTEGER NOT NULL,
+                dep_key TEXT NOT NULL,
+                generation INTEGER NOT NULL,
+                stage INTEGER NOT NULL,
+                PRIMARY KEY (operation_id, dep_key)
+             );
+             CREATE INDEX IF NOT EXISTS idx_operation_dependencies_key
+               ON operation_dependencies(dep_key, generation, stage, operation_id);",
+        )?;
+
+        let queued: Vec<(i64, String, String)> = {
+            let mut stmt = tx.prepare(
+                "SELECT id, operation_type, payload FROM pending_operations
+                 UNION ALL
+                 SELECT id, operation_type, payload FROM failed_operations
+                 ORDER BY id",
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            rows.collect::<SqlResult<_>>()?
+        };
+        for (id, operation_type, payload) in queued {
+            // Payloads antigos fora do esquema seguem sem dependências
+            if let Ok(operation) = Operation::parse(&operation_type, &payload) {
+                Self::record_operation_dependencies(tx, id, &operation)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Migration 15: projeção das operações pendentes sobre rfid_items. As
+    // operações já na fila passam a ser projetadas imediatamente.
+    fn add_rfid_item_overlay(tx: &rusqlite::Transaction) -> SqlResult<()> {
+        tx.execute_batch(
+            "CREATE TABLE IF NOT EXISTS rfid_item_overlay (
+                operation_id INTEGER NOT NULL,
+                item_id TEXT NOT NULL,
+                status TEXT NOT NULL,
+                PRIMARY KEY (operation_id, item_id)
+             );
+             CREATE INDEX IF NOT EXISTS idx_rfid_item_overlay_item
+               ON rfid_item_overlay(item_id, operation_id);",
+        )?;
+
+        let queued: Vec<(i64, String, String)> = {
+            let mut stmt = tx.prepare(
+                "SELECT id, operation_type, payload FROM pending_operations ORDER BY id",
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            rows.collect::<SqlResult<_>>()?
+        };
+        for (id, operation_type, payload) in queued {
+            if let Ok(operation) = Operation::parse(&operation_type, &payload) {
+                Self::project_operation(tx, id, &operation)?;
+            }
+        }
         Ok(())
     }
 
+    fn schema_version(conn: &Connection) -> SqlResult<i64> {
+        conn.query_row(
+            "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+            [],
+            |row| row.get(0),
+        )
+    }
+
     // ==================== RFID ITEMS ====================
     
+    const RFID_ITEM_COLUMNS: &'static str =
+        "id, tag, tid, linen_item_id, linen_item_name, linen_item_sku,
+         full_number, batch_number, piece_number, status, client_id, client_name, updated_at";
+
+    fn row_to_rfid_item(row: &rusqlite::Row) -> SqlResult<RfidItem> {
+        Self::row_to_rfid_item_at(row, 0)
+    }
+
+    // ==================== PENDING OVERLAY ====================
+
+    // Grava o status que a operação projeta sobre cada peça conhecida no cache
+    fn project_operation(conn: &Connection, id: i64, operation: &Operation) -> SqlResult<()> {
+        let Some(status) = operation.projected_status() else {
+            return Ok(());
+        };
+
+        for key in operation.dependency_keys() {
+            let (sql, value) = if let Some(tag) = key.strip_prefix("tag:") {
+                ("INSERT OR IGNORE INTO rfid_item_overlay (operation_id, item_id, status)
+                  SELECT ?1, id, ?3 FROM rfid_items WHERE tag = ?2", tag)
+            } else if let Some(item_id) = key.strip_prefix("item:") {
+                ("INSERT OR IGNORE INTO rfid_item_overlay (operation_id, item_id, status)
+                  SELECT ?1, id, ?3 FROM rfid_items WHERE id = ?2", item_id)
+            } else {
+                continue;
+            };
+            conn.execute(sql, params![id, value, status])?;
+        }
+        Ok(())
+    }
+
+    // A operação mais recente da fila define o status exibido
+    fn apply_pending_overlay(conn: &Connection, item: &mut RfidItem) -> SqlResult<()> {
+        let projected = conn.query_row(
+            "SELECT status FROM rfid_item_overlay
+             WHERE item_id = ?1
+             ORDER BY operation_id DESC
+             LIMIT 1",
+            params![item.id],
+            |row| row.get::<_, String>(0),
+        );
+        match projected {
+            Ok(status) => {
+                item.status = Some(status);
+                item.pending_sync = true;
+                Ok(())
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Servidor aceitou a operação: o status projetado passa ao cache, sem
+    // mexer no updated_at, e o próximo pull o confirma ou corrige
+    fn settle_projection(conn: &Connection, id: i64) -> SqlResult<()> {
+        conn.execute(
+            "UPDATE rfid_items
+             SET status = (SELECT o.status FROM rfid_item_overlay o
+                           WHERE o.operation_id = ?1 AND o.item_id = rfid_items.id)
+             WHERE id IN (SELECT item_id FROM rfid_item_overlay WHERE operation_id = ?1)",
+            params![id],
+        )?;
+        Self::discard_projection(conn, id)
+    }
+
+    fn discard_projection(conn: &Connection, id: i64) -> SqlResult<()> {
+        conn.execute("DELETE FROM rfid_item_overlay WHERE operation_id = ?1", params![id])?;
+        Ok(())
+    }
+
     pub fn lookup_rfid_item(&self, tag: &str) -> SqlResult<Option<RfidItem>> {
+        // Uma tag inválida nunca está no cache
+        let Ok(normalized) = tag::normalize_tag(tag) else {
+            return Ok(None);
+        };
         let conn = self.conn.lock().unwrap();
-        let normalized = tag.to_uppercase().replace(" ", "");
         
-        let mut stmt = conn.prepare(
-            "SELECT id, tag, tid, linen_item_id, linen_item_name, linen_item_sku, 
-                    full_number, batch_number, piece_number, status, client_id, client_name, updated_at
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
              FROM rfid_items 
              WHERE tag = ?1 OR tid = ?1 
              ORDER BY updated_at DESC
-             LIMIT 1"
-        )?;
+             LIMIT 1",
+            Self::RFID_ITEM_COLUMNS
+        ))?;
         
-        let result = stmt.query_row(params![normalized], |row| {
-            Ok(RfidItem {
-                id: row.get(0)?,
-                tag: row.get(1)?,
-                tid: row.get(2)?,
-                linen_item_id: row.get(3)?,
-                linen_item_name: row.get(4)?,
-                linen_item_sku: row.get(5)?,
-                full_number: row.get(6)?,
-                batch_number: row.get(7)?,
-                piece_number: row.get(8)?,
-                status: row.get(9)?,
-                client_id: row.get(10)?,
-                client_name: row.get(11)?,
-                updated_at: row.get(12)?,
-            })
-        });
+        let result = stmt.query_row(params![normalized], Self::row_to_rfid_item);
         
         match result {
-            Ok(item) => Ok(Some(item)),
+            Ok(mut item) => {
+                Self::apply_pending_overlay(&conn, &mut item)?;
+                Ok(Some(item))
+            }
             Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
             Err(e) => Err(e),
         }
     }
 
+    // Resolve várias tags em uma única ida ao banco (leituras de portal/túnel).
+    // As tags entram numa tabela temporária e são cruzadas por tag e por TID.
+    pub fn lookup_rfid_items_bulk(&self, tags: &[String]) -> SqlResult<BulkLookupResult> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        tx.execute_batch(
+            "CREATE TEMP TABLE IF NOT EXISTS bulk_lookup (
+               input TEXT PRIMARY KEY,
+               normalized TEXT NOT NULL
+             );
+             DELETE FROM temp.bulk_lookup;",
+        )?;
+
+        let mut inputs = Vec::with_capacity(tags.len());
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO temp.bulk_lookup (input, normalized) VALUES (?1, ?2)"
+            )?;
+            let mut seen = std::collections::HashSet::new();
+            for input in tags {
+                if !seen.insert(input.as_str()) {
+                    continue;
+                }
+                inputs.push(input.clone());
+                if let Ok(normalized) = tag::normalize_tag(input) {
+                    stmt.execute(params![input, normalized])?;
+                }
+            }
+        }
+
+        let mut found: HashMap<String, RfidItem> = HashMap::new();
+        {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT l.input, {cols} FROM temp.bulk_lookup l
+                   JOIN rfid_items r ON r.tag = l.normalized
+                 UNION ALL
+                 SELECT l.input, {cols} FROM temp.bulk_lookup l
+                   JOIN rfid_items r ON r.tid = l.normalized",
+                cols = "r.id, r.tag, r.tid, r.linen_item_id, r.linen_item_name, r.linen_item_sku,
+                        r.full_number, r.batch_number, r.piece_number, r.status, r.client_id,
+                        r.client_name, r.updated_at"
+            ))?;
+
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let input: String = row.get(0)?;
+                let item = Self::row_to_rfid_item_at(row, 1)?;
+                // Mesmo critério do lookup unitário: vence o registro mais recente
+                match found.get(&input) {
+                    Some(existing) if existing.updated_at >= item.updated_at => {}
+                    _ => {
+                        found.insert(input, item);
+                    }
+                }
+            }
+        }
+
+        for item in found.values_mut() {
+            Self::apply_pending_overlay(&tx, item)?;
+        }
+
+        tx.execute("DELETE FROM temp.bulk_lookup", [])?;
+        tx.commit()?;
+
+        let unknown = inputs
+            .into_iter()
+            .filter(|tag| !found.contains_key(tag))
+            .collect();
+
+        Ok(BulkLookupResult { found, unknown })
+    }
+
+    fn row_to_rfid_item_at(row: &rusqlite::Row, offset: usize) -> SqlResult<RfidItem> {
+        Ok(RfidItem {
+            id: row.get(offset)?,
+            // Peças desanexadas não têm tag
+            tag: row.get::<_, Option<String>>(offset + 1)?.unwrap_or_default(),
+            tid: row.get(offset + 2)?,
+            linen_item_id: row.get(offset + 3)?,
+            linen_item_name: row.get(offset + 4)?,
+            linen_item_sku: row.get(offset + 5)?,
+            full_number: row.get(offset + 6)?,
+            batch_number: row.get(offset + 7)?,
+            piece_number: row.get(offset + 8)?,
+            status: row.get(offset + 9)?,
+            client_id: row.get(offset + 10)?,
+            client_name: row.get(offset + 11)?,
+            updated_at: row.get(offset + 12)?,
+            pending_sync: false,
+        })
+    }
+
+    // Tag e TID sempre gravados na forma canônica
+    fn canonical_rfid_item(item: &RfidItem) -> Result<RfidItem, TagError> {
+        let mut item = item.clone();
+        item.tag = tag::normalize_tag(&item.tag)?;
+        item.tid = tag::normalize_optional_tag(item.tid.as_deref())?;
+        Ok(item)
+    }
+
+    fn tag_error(e: TagError) -> rusqlite::Error {
+        rusqlite::Error::ToSqlConversionFailure(Box::new(e))
+    }
+
     pub fn upsert_rfid_item(&self, item: &RfidItem) -> SqlResult<()> {
+        let item = Self::canonical_rfid_item(item).map_err(Self::tag_error)?;
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        
+        Self::upsert_rfid_item_tx(&tx, &item, "sync")?;
+        
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn bulk_upsert_rfid_items(&self, items: &[RfidItem]) -> SqlResult<RfidSyncSummary> {
         let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        
+        let mut summary = RfidSyncSummary::default();
+        Self::upsert_rfid_items_tx(&tx, items, &mut summary)?;
         
+        tx.commit()?;
+        Ok(summary)
+    }
+
+    // Uma linha com tag/TID inválidos vinda do servidor não pode travar o
+    // lote inteiro (e com ele a sincronização): é pulada e relatada.
+    fn upsert_rfid_items_tx(
+        conn: &Connection,
+        items: &[RfidItem],
+        summary: &mut RfidSyncSummary,
+    ) -> SqlResult<()> {
+        for item in items {
+            match Self::canonical_rfid_item(item) {
+                Ok(item) => {
+                    Self::upsert_rfid_item_tx(conn, &item, "sync")?;
+                    summary.upserted += 1;
+                }
+                Err(e) => summary.rejected.push(RejectedRfidItem {
+                    id: item.id.clone(),
+                    tag: item.tag.clone(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+        Ok(())
+    }
+
+    // Aplica um lote incremental numa única transação. As remoções entram
+    // primeiro, para que um item do mesmo lote com updated_at anterior à
+    // remoção seja ignorado.
+    pub fn apply_rfid_sync(&self, batch: &RfidSyncBatch) -> SqlResult<RfidSyncSummary> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        let mut summary = RfidSyncSummary::default();
+        for tombstone in &batch.deleted {
+            if Self::apply_rfid_tombstone(&tx, tombstone)? {
+                summary.deleted += 1;
+            }
+        }
+        Self::upsert_rfid_items_tx(&tx, &batch.items, &mut summary)?;
+
+        // O cursor avança na mesma transação dos dados: um crash no meio não
+        // pula alterações no próximo pull. Linhas rejeitadas também avançam o
+        // cursor; voltam quando o servidor as corrigir.
+        let high_water_mark = batch
+            .items
+            .iter()
+            .map(|item| item.updated_at)
+            .chain(batch.deleted.iter().map(|tombstone| tombstone.deleted_at))
+            .max();
+        Self::record_sync(
+            &tx,
+            Self::RFID_SYNC_ENTITY,
+            (summary.upserted + summary.deleted) as i64,
+            batch.cursor.as_deref(),
+            high_water_mark,
+        )?;
+
+        tx.commit()?;
+        Ok(summary)
+    }
+
+    fn apply_rfid_tombstone(conn: &Connection, tombstone: &RfidTombstone) -> SqlResult<bool> {
+        conn.execute(
+            "INSERT INTO rfid_tombstones (id, deleted_at) VALUES (?1, ?2)
+             ON CONFLICT(id) DO UPDATE SET deleted_at = MAX(deleted_at, excluded.deleted_at)",
+            params![tombstone.id, tombstone.deleted_at],
+        )?;
+
+        let removed = conn.execute(
+            "DELETE FROM rfid_items WHERE id = ?1 AND updated_at <= ?2",
+            params![tombstone.id, tombstone.deleted_at],
+        )?;
+        // Remoção mais antiga que o cache não apaga a peça nem encerra a tag dela
+        if removed > 0 {
+            Self::close_tag_history(conn, "item_id = ?1", &tombstone.id, tombstone.deleted_at, "sync")?;
+        }
+
+        Ok(removed > 0)
+    }
+
+    // Grava um item resolvendo movimentações de tag: se a tag pertence a outra
+    // peça, vence o updated_at mais novo. A peça perdedora fica desanexada
+    // (tag/tid NULL) e a movimentação é registrada em tag_moves.
+    fn upsert_rfid_item_tx(conn: &Connection, item: &RfidItem, source: &str) -> SqlResult<()> {
+        // Peça removida no servidor depois desta versão: não ressuscitar
+        let tombstoned: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM rfid_tombstones WHERE id = ?1 AND deleted_at >= ?2)",
+            params![item.id, item.updated_at],
+            |row| row.get(0),
+        )?;
+        if tombstoned {
+            return Ok(());
+        }
+
+        // Versão mais antiga que a do cache: o ON CONFLICT abaixo seria
+        // ignorado, então a dona atual da tag também não pode perdê-la
+        let stale: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM rfid_items WHERE id = ?1 AND updated_at > ?2)",
+            params![item.id, item.updated_at],
+            |row| row.get(0),
+        )?;
+        if stale {
+            return Ok(());
+        }
+
+        let owner = conn.query_row(
+            "SELECT id, updated_at FROM rfid_items WHERE tag = ?1 AND id <> ?2",
+            params![item.tag, item.id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+        );
+        let owner = match owner {
+            Ok(owner) => Some(owner),
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(e),
+        };
+
+        let mut tag = Some(item.tag.as_str());
+        let mut tid = item.tid.as_deref();
+        if let Some((owner_id, owner_updated_at)) = owner {
+            if item.updated_at >= owner_updated_at {
+                Self::detach_rfid_row(conn, &owner_id, item.updated_at, source)?;
+                Self::record_tag_move(
+                    conn, &item.tag, Some(&owner_id), Some(&item.id), item.updated_at, source,
+                )?;
+            } else {
+                // Informação antiga: a tag já está com outra peça mais recente
+                tag = None;
+                tid = None;
+            }
+        }
+
         conn.execute(
             "INSERT INTO rfid_items 
              (id, tag, tid, linen_item_id, linen_item_name, linen_item_sku, 
-              full_number, batch_number, piece_number, status, client_id, client_name, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+              full_number, batch_number, piece_number, status, client_id, client_name, updated_at,
+              detached_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13,
+                     CASE WHEN ?2 IS NULL THEN ?13 END)
              ON CONFLICT(id) DO UPDATE SET
                 tag = excluded.tag,
                 tid = excluded.tid,
@@ -113,185 +833,2730 @@ impl Database {
                 status = excluded.status,
                 client_id = excluded.client_id,
                 client_name = excluded.client_name,
-                updated_at = excluded.updated_at",
+                updated_at = excluded.updated_at,
+                detached_at = CASE
+                    WHEN excluded.tag IS NULL THEN COALESCE(rfid_items.detached_at, excluded.updated_at)
+                END
+             WHERE excluded.updated_at >= rfid_items.updated_at",
             params![
-                item.id, item.tag, item.tid, item.linen_item_id, item.linen_item_name,
+                item.id, tag, tid, item.linen_item_id, item.linen_item_name,
                 item.linen_item_sku, item.full_number, item.batch_number, item.piece_number,
                 item.status, item.client_id, item.client_name, item.updated_at
             ],
         )?;
-        
+
+        // Nada mudou quando a atualização era mais antiga que o cache
+        if conn.changes() > 0 {
+            Self::record_tag_association(conn, &item.id, tag, tid, item.updated_at, source)?;
+        }
+
         Ok(())
     }
 
-    pub fn bulk_upsert_rfid_items(&self, items: &[RfidItem]) -> SqlResult<usize> {
-        let conn = self.conn.lock().unwrap();
-        let tx = conn.unchecked_transaction()?;
-        
-        let mut count = 0;
-        for item in items {
-            tx.execute(
-                "INSERT INTO rfid_items 
-                 (id, tag, tid, linen_item_id, linen_item_name, linen_item_sku, 
-                  full_number, batch_number, piece_number, status, client_id, client_name, updated_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
-                 ON CONFLICT(id) DO UPDATE SET
-                    tag = excluded.tag,
-                    tid = excluded.tid,
-                    linen_item_id = excluded.linen_item_id,
-                    linen_item_name = excluded.linen_item_name,
-                    linen_item_sku = excluded.linen_item_sku,
-                    full_number = excluded.full_number,
-                    batch_number = excluded.batch_number,
-                    piece_number = excluded.piece_number,
-                    status = excluded.status,
-                    client_id = excluded.client_id,
-                    client_name = excluded.client_name,
-                    updated_at = excluded.updated_at",
-                params![
-                    item.id, item.tag, item.tid, item.linen_item_id, item.linen_item_name,
-                    item.linen_item_sku, item.full_number, item.batch_number, item.piece_number,
-                    item.status, item.client_id, item.client_name, item.updated_at
-                ],
+    // Mantém tag_history coerente com a associação atual da peça: fecha o período
+    // anterior (se a tag mudou) e abre um novo para a tag recebida.
+    fn record_tag_association(
+        conn: &Connection,
+        item_id: &str,
+        tag: Option<&str>,
+        tid: Option<&str>,
+        at: i64,
+        source: &str,
+    ) -> SqlResult<()> {
+        let current = conn.query_row(
+            "SELECT tag FROM tag_history WHERE item_id = ?1 AND detached_at IS NULL",
+            params![item_id],
+            |row| row.get::<_, String>(0),
+        );
+        let current = match current {
+            Ok(tag) => Some(tag),
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(e),
+        };
+
+        if current.as_deref() == tag {
+            if let Some(tag) = tag {
+                conn.execute(
+                    "UPDATE tag_history SET tid = COALESCE(?3, tid)
+                     WHERE item_id = ?1 AND tag = ?2 AND detached_at IS NULL",
+                    params![item_id, tag, tid],
+                )?;
+            }
+            return Ok(());
+        }
+
+        Self::close_tag_history(conn, "item_id = ?1", item_id, at, source)?;
+        if let Some(tag) = tag {
+            Self::close_tag_history(conn, "tag = ?1", tag, at, source)?;
+            conn.execute(
+                "INSERT INTO tag_history (tag, tid, item_id, associated_at, source)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![tag, tid, item_id, at, source],
             )?;
-            count += 1;
         }
-        
-        tx.commit()?;
-        Ok(count)
+
+        Ok(())
     }
 
-    // ==================== PENDING OPERATIONS ====================
-    
-    pub fn queue_operation(&self, operation_type: &str, payload: &str) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
-        let now = chrono::Utc::now().timestamp();
-        
+    fn close_tag_history(
+        conn: &Connection,
+        filter: &str,
+        value: &str,
+        at: i64,
+        source: &str,
+    ) -> SqlResult<()> {
         conn.execute(
-            "INSERT INTO pending_operations (operation_type, payload, created_at) 
-             VALUES (?1, ?2, ?3)",
-            params![operation_type, payload, now],
+            &format!(
+                "UPDATE tag_history SET detached_at = MAX(?2, associated_at), detached_source = ?3
+                 WHERE {} AND detached_at IS NULL",
+                filter
+            ),
+            params![value, at, source],
         )?;
-        
-        Ok(conn.last_insert_rowid())
+        Ok(())
     }
 
-    pub fn get_pending_operations(&self, limit: usize) -> SqlResult<Vec<PendingOperation>> {
+    pub fn get_tag_history(&self, tag: &str) -> SqlResult<Vec<TagHistoryEntry>> {
+        let tag = tag::normalize_tag(tag).map_err(Self::tag_error)?;
+        self.query_tag_history("tag = ?1", &tag)
+    }
+
+    pub fn get_item_tag_history(&self, item_id: &str) -> SqlResult<Vec<TagHistoryEntry>> {
+        self.query_tag_history("item_id = ?1", item_id)
+    }
+
+    fn query_tag_history(&self, filter: &str, value: &str) -> SqlResult<Vec<TagHistoryEntry>> {
         let conn = self.conn.lock().unwrap();
-        
-        let mut stmt = conn.prepare(
-            "SELECT id, operation_type, payload, created_at, retry_count, last_error
-             FROM pending_operations
-             ORDER BY created_at ASC
-             LIMIT ?1"
-        )?;
-        
-        let ops = stmt.query_map(params![limit], |row| {
-            Ok(PendingOperation {
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, tag, tid, item_id, associated_at, detached_at, source, detached_source
+             FROM tag_history
+             WHERE {}
+             ORDER BY associated_at ASC, id ASC",
+            filter
+        ))?;
+
+        let entries = stmt.query_map(params![value], |row| {
+            Ok(TagHistoryEntry {
                 id: row.get(0)?,
-                operation_type: row.get(1)?,
-                payload: row.get(2)?,
-                created_at: row.get(3)?,
-                retry_count: row.get(4)?,
-                last_error: row.get(5)?,
+                tag: row.get(1)?,
+                tid: row.get(2)?,
+                item_id: row.get(3)?,
+                associated_at: row.get(4)?,
+                detached_at: row.get(5)?,
+                source: row.get(6)?,
+                detached_source: row.get(7)?,
             })
         })?;
-        
-        ops.collect()
+
+        entries.collect()
     }
 
-    pub fn delete_operation(&self, id: i64) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM pending_operations WHERE id = ?1", params![id])?;
-        Ok(())
+    fn detach_rfid_row(conn: &Connection, id: &str, at: i64, source: &str) -> SqlResult<()> {
+        conn.execute(
+            "UPDATE rfid_items SET tag = NULL, tid = NULL, detached_at = ?2 WHERE id = ?1",
+            params![id, at],
+        )?;
+        Self::close_tag_history(conn, "item_id = ?1", id, at, source)
     }
 
-    pub fn increment_retry_count(&self, id: i64, error: &str) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        let now = chrono::Utc::now().timestamp();
-        
+    fn record_tag_move(
+        conn: &Connection,
+        tag: &str,
+        from_item_id: Option<&str>,
+        to_item_id: Option<&str>,
+        moved_at: i64,
+        source: &str,
+    ) -> SqlResult<()> {
         conn.execute(
-            "UPDATE pending_operations 
-             SET retry_count = retry_count + 1, last_error = ?2, last_retry_at = ?3
-             WHERE id = ?1",
-            params![id, error, now],
+            "INSERT INTO tag_moves (tag, from_item_id, to_item_id, moved_at, recorded_at, source)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![tag, from_item_id, to_item_id, moved_at, chrono::Utc::now().timestamp(), source],
         )?;
-        
         Ok(())
     }
 
-    pub fn get_pending_count(&self) -> SqlResult<i64> {
+    // Liberação de tag feita no totem (fluxo RFID_DETACH_TAG)
+    pub fn detach_rfid_tag(&self, tag: &str, at: i64) -> SqlResult<Option<String>> {
+        let tag = tag::normalize_tag(tag).map_err(Self::tag_error)?;
         let conn = self.conn.lock().unwrap();
-        let count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM pending_operations",
-            [],
+        let tx = conn.unchecked_transaction()?;
+
+        let owner = tx.query_row(
+            "SELECT id FROM rfid_items WHERE tag = ?1",
+            params![tag],
+            |row| row.get::<_, String>(0),
+        );
+        let owner_id = match owner {
+            Ok(id) => id,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        Self::detach_rfid_row(&tx, &owner_id, at, "local")?;
+        tx.execute(
+            "UPDATE rfid_items SET updated_at = MAX(updated_at, ?2) WHERE id = ?1",
+            params![owner_id, at],
+        )?;
+        Self::record_tag_move(&tx, &tag, Some(&owner_id), None, at, "local")?;
+
+        tx.commit()?;
+        Ok(Some(owner_id))
+    }
+
+    // Troca de tag feita no totem (fluxo RFID_REPLACE_TAG): a peça recebe a nova
+    // tag e, se essa tag estava com outra peça, a outra é desanexada. Retorna
+    // false se a peça não está no cache e erro (TagConflict) se a outra peça
+    // foi alterada depois de `at`.
+    pub fn replace_rfid_tag(
+        &self,
+        item_id: &str,
+        new_tag: &str,
+        new_tid: Option<&str>,
+        at: i64,
+    ) -> SqlResult<bool> {
+        let new_tag = tag::normalize_tag(new_tag).map_err(Self::tag_error)?;
+        let new_tid = tag::normalize_optional_tag(new_tid).map_err(Self::tag_error)?;
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        let current = tx.query_row(
+            &format!("SELECT {} FROM rfid_items WHERE id = ?1", Self::RFID_ITEM_COLUMNS),
+            params![item_id],
+            Self::row_to_rfid_item,
+        );
+        let mut item = match current {
+            Ok(item) => item,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        // Se a outra peça é mais recente, o upsert deixaria esta sem tag
+        // nenhuma (perde a antiga e não recebe a nova): recusa tudo antes
+        let updated_at = at.max(item.updated_at);
+        let owner = tx.query_row(
+            "SELECT id, updated_at FROM rfid_items WHERE tag = ?1 AND id <> ?2",
+            params![new_tag, item_id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+        );
+        match owner {
+            Ok((owner_id, owner_updated_at)) if owner_updated_at > updated_at => {
+                return Err(rusqlite::Error::ToSqlConversionFailure(Box::new(TagConflict {
+                    tag: new_tag,
+                    owner_id,
+                })));
+            }
+            Ok(_) | Err(rusqlite::Error::QueryReturnedNoRows) => {}
+            Err(e) => return Err(e),
+        }
+
+        let old_tag = item.tag.clone();
+        item.tag = new_tag;
+        item.tid = new_tid;
+        item.updated_at = updated_at;
+        Self::upsert_rfid_item_tx(&tx, &item, "local")?;
+        if !old_tag.is_empty() && old_tag != item.tag {
+            Self::record_tag_move(&tx, &old_tag, Some(item_id), None, item.updated_at, "local")?;
+        }
+
+        tx.commit()?;
+        Ok(true)
+    }
+
+    // ==================== RFID SEARCH ====================
+
+    // Transforma o texto digitado em uma expressão FTS5 de prefixos:
+    // "lencol 0012" => "lencol"* "0012"* (todos os termos precisam casar)
+    fn fts_prefix_query(text: &str) -> Option<String> {
+        let terms: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|term| !term.is_empty())
+            .map(|term| format!("\"{}\"*", term))
+            .collect();
+
+        if terms.is_empty() {
+            None
+        } else {
+            Some(terms.join(" "))
+        }
+    }
+
+    // Busca por número impresso, SKU, nome do produto ou parte da tag.
+    // Resultados ordenados por relevância (bm25, com peso maior para full_number).
+    pub fn search_rfid_items(&self, query: &RfidSearchQuery) -> SqlResult<RfidSearchResult> {
+        let Some(expression) = Self::fts_prefix_query(&query.text) else {
+            return Ok(RfidSearchResult { items: Vec::new(), total: 0 });
+        };
+        let limit = query.limit.unwrap_or(50).min(500) as i64;
+        let offset = query.offset.unwrap_or(0) as i64;
+
+        let conn = self.conn.lock().unwrap();
+        // O filtro de status vê o mesmo status exibido: o projetado pela fila,
+        // quando houver (ver apply_pending_overlay)
+        let filters = "rfid_items_fts MATCH ?1
+               AND r.tag IS NOT NULL
+               AND (?2 IS NULL OR r.client_id = ?2)
+               AND (?3 IS NULL OR COALESCE(
+                     (SELECT o.status FROM rfid_item_overlay o
+                      WHERE o.item_id = r.id
+                      ORDER BY o.operation_id DESC
+                      LIMIT 1),
+                     r.status) = ?3)";
+
+        let total: i64 = conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM rfid_items_fts
+                 JOIN rfid_items r ON r.seq = rfid_items_fts.rowid
+                 WHERE {}",
+                filters
+            ),
+            params![expression, query.client_id, query.status],
             |row| row.get(0),
         )?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT r.id, r.tag, r.tid, r.linen_item_id, r.linen_item_name, r.linen_item_sku,
+                    r.full_number, r.batch_number, r.piece_number, r.status, r.client_id,
+                    r.client_name, r.updated_at
+             FROM rfid_items_fts
+             JOIN rfid_items r ON r.seq = rfid_items_fts.rowid
+             WHERE {}
+             ORDER BY bm25(rfid_items_fts, 10.0, 5.0, 1.0, 2.0), r.updated_at DESC
+             LIMIT ?4 OFFSET ?5",
+            filters
+        ))?;
+
+        let mut items = stmt
+            .query_map(
+                params![expression, query.client_id, query.status, limit, offset],
+                Self::row_to_rfid_item,
+            )?
+            .collect::<SqlResult<Vec<_>>>()?;
+        for item in &mut items {
+            Self::apply_pending_overlay(&conn, item)?;
+        }
+
+        Ok(RfidSearchResult { items, total })
+    }
+
+    // ==================== LINEN ITEMS ====================
+
+    const LINEN_ITEM_UPSERT: &'static str =
+        "INSERT INTO linen_items (id, name, sku, client_id, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(id) DO UPDATE SET
+            name = excluded.name,
+            sku = excluded.sku,
+            client_id = excluded.client_id,
+            updated_at = excluded.updated_at
+         WHERE excluded.updated_at >= linen_items.updated_at";
+
+    fn row_to_linen_item(row: &rusqlite::Row) -> SqlResult<LinenItem> {
+        Ok(LinenItem {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            sku: row.get(2)?,
+            client_id: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    }
+
+    pub fn upsert_linen_item(&self, item: &LinenItem) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            Self::LINEN_ITEM_UPSERT,
+            params![item.id, item.name, item.sku, item.client_id, item.updated_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn bulk_upsert_linen_items(&self, items: &[LinenItem]) -> SqlResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        let mut count = 0;
+        {
+            let mut stmt = tx.prepare(Self::LINEN_ITEM_UPSERT)?;
+            for item in items {
+                stmt.execute(params![item.id, item.name, item.sku, item.client_id, item.updated_at])?;
+                count += 1;
+            }
+        }
+
+        tx.commit()?;
         Ok(count)
     }
 
-    // ==================== SYNC LOG ====================
-    
-    pub fn get_last_sync(&self, entity: &str) -> SqlResult<i64> {
+    // Produtos do cliente + produtos sem cliente (catálogo compartilhado)
+    pub fn list_linen_items_by_client(&self, client_id: &str) -> SqlResult<Vec<LinenItem>> {
         let conn = self.conn.lock().unwrap();
-        
+
+        let mut stmt = conn.prepare(
+            "SELECT id, name, sku, client_id, updated_at
+             FROM linen_items
+             WHERE client_id = ?1 OR client_id IS NULL
+             ORDER BY name COLLATE NOCASE ASC"
+        )?;
+
+        let items = stmt.query_map(params![client_id], Self::row_to_linen_item)?;
+        items.collect()
+    }
+
+    pub fn get_linen_item(&self, id: &str) -> SqlResult<Option<LinenItem>> {
+        let conn = self.conn.lock().unwrap();
+
         let result = conn.query_row(
-            "SELECT last_sync_at FROM sync_log WHERE entity = ?1",
-            params![entity],
-            |row| row.get(0),
+            "SELECT id, name, sku, client_id, updated_at FROM linen_items WHERE id = ?1",
+            params![id],
+            Self::row_to_linen_item,
         );
-        
+
         match result {
-            Ok(timestamp) => Ok(timestamp),
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(0),
+            Ok(item) => Ok(Some(item)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
             Err(e) => Err(e),
         }
     }
 
-    pub fn update_sync_log(&self, entity: &str, count: i64) -> SqlResult<()> {
+    // ==================== SECTORS & BEDS ====================
+
+    const SECTOR_COLUMNS: &'static str = "id, name, description, client_id, updated_at";
+    const BED_COLUMNS: &'static str = "id, number, sector_id, status, client_id, token, updated_at";
+
+    fn row_to_sector(row: &rusqlite::Row) -> SqlResult<Sector> {
+        Ok(Sector {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            description: row.get(2)?,
+            client_id: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    }
+
+    fn row_to_bed(row: &rusqlite::Row) -> SqlResult<Bed> {
+        Ok(Bed {
+            id: row.get(0)?,
+            number: row.get(1)?,
+            sector_id: row.get(2)?,
+            status: row.get(3)?,
+            client_id: row.get(4)?,
+            token: row.get(5)?,
+            updated_at: row.get(6)?,
+        })
+    }
+
+    pub fn bulk_upsert_sectors(&self, sectors: &[Sector]) -> SqlResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        let mut count = 0;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO sectors (id, name, description, client_id, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT(id) DO UPDATE SET
+                    name = excluded.name,
+                    description = excluded.description,
+                    client_id = excluded.client_id,
+                    updated_at = excluded.updated_at
+                 WHERE excluded.updated_at >= sectors.updated_at"
+            )?;
+            for sector in sectors {
+                stmt.execute(params![
+                    sector.id, sector.name, sector.description, sector.client_id, sector.updated_at
+                ])?;
+                count += 1;
+            }
+        }
+
+        tx.commit()?;
+        Ok(count)
+    }
+
+    pub fn bulk_upsert_beds(&self, beds: &[Bed]) -> SqlResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        let mut count = 0;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO beds (id, number, sector_id, status, client_id, token, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT(id) DO UPDATE SET
+                    number = excluded.number,
+                    sector_id = excluded.sector_id,
+                    status = excluded.status,
+                    client_id = excluded.client_id,
+                    token = excluded.token,
+                    updated_at = excluded.updated_at
+                 WHERE excluded.updated_at >= beds.updated_at"
+            )?;
+            for bed in beds {
+                stmt.execute(params![
+                    bed.id, bed.number, bed.sector_id, bed.status, bed.client_id, bed.token,
+                    bed.updated_at
+                ])?;
+                count += 1;
+            }
+        }
+
+        tx.commit()?;
+        Ok(count)
+    }
+
+    pub fn list_sectors_by_client(&self, client_id: &str) -> SqlResult<Vec<Sector>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM sectors WHERE client_id = ?1 ORDER BY name COLLATE NOCASE ASC",
+            Self::SECTOR_COLUMNS
+        ))?;
+
+        let sectors = stmt.query_map(params![client_id], Self::row_to_sector)?;
+        sectors.collect()
+    }
+
+    // Leitos do cliente, opcionalmente filtrados por setor e status ('free' / 'occupied')
+    pub fn list_beds(
+        &self,
+        client_id: &str,
+        sector_id: Option<&str>,
+        status: Option<&str>,
+    ) -> SqlResult<Vec<Bed>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM beds
+             WHERE client_id = ?1
+               AND (?2 IS NULL OR sector_id = ?2)
+               AND (?3 IS NULL OR status = ?3)
+             ORDER BY sector_id, CAST(number AS INTEGER), number",
+            Self::BED_COLUMNS
+        ))?;
+
+        let beds = stmt.query_map(params![client_id, sector_id, status], Self::row_to_bed)?;
+        beds.collect()
+    }
+
+    pub fn get_bed_by_token(&self, token: &str) -> SqlResult<Option<BedLocation>> {
+        let conn = self.conn.lock().unwrap();
+
+        let result = conn.query_row(
+            &format!("SELECT {} FROM beds WHERE token = ?1", Self::BED_COLUMNS),
+            params![token.trim()],
+            Self::row_to_bed,
+        );
+
+        let bed = match result {
+            Ok(bed) => bed,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let sector = match &bed.sector_id {
+            Some(sector_id) => {
+                let result = conn.query_row(
+                    &format!("SELECT {} FROM sectors WHERE id = ?1", Self::SECTOR_COLUMNS),
+                    params![sector_id],
+                    Self::row_to_sector,
+                );
+                match result {
+                    Ok(sector) => Some(sector),
+                    Err(rusqlite::Error::QueryReturnedNoRows) => None,
+                    Err(e) => return Err(e),
+                }
+            }
+            None => None,
+        };
+
+        Ok(Some(BedLocation { bed, sector }))
+    }
+
+    // Setores do cliente com os respectivos leitos (filtro de status opcional)
+    pub fn get_sector_tree(
+        &self,
+        client_id: &str,
+        status: Option<&str>,
+    ) -> SqlResult<Vec<SectorWithBeds>> {
+        let sectors = self.list_sectors_by_client(client_id)?;
+        let beds = self.list_beds(client_id, None, status)?;
+
+        Ok(sectors
+            .into_iter()
+            .map(|sector| {
+                let beds = beds
+                    .iter()
+                    .filter(|bed| bed.sector_id.as_deref() == Some(sector.id.as_str()))
+                    .cloned()
+                    .collect();
+                SectorWithBeds { sector, beds }
+            })
+            .collect())
+    }
+
+    // ==================== PENDING OPERATIONS ====================
+    
+    // Tags no payload seguem a mesma forma canônica do cache
+    fn parse_operation(operation_type: &str, payload: &str) -> SqlResult<Operation> {
+        Operation::parse(operation_type, payload)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+    }
+
+    fn new_idempotency_key() -> String {
+        uuid::Uuid::new_v4().to_string()
+    }
+
+    // Chaves de dependência da operação completadas pelo cache local: tags e
+    // peças se referem ao mesmo objeto
+    fn linked_dependency_keys(conn: &Connection, operation: &Operation) -> SqlResult<Vec<String>> {
+        let mut keys = operation.dependency_keys();
+        for key in keys.clone() {
+            let linked: SqlResult<Option<String>> = if let Some(tag) = key.strip_prefix("tag:") {
+                conn.query_row(
+                    "SELECT 'item:' || id FROM rfid_items WHERE tag = ?1",
+                    params![tag],
+                    |row| row.get(0),
+                )
+            } else if let Some(item_id) = key.strip_prefix("item:") {
+                conn.query_row(
+                    "SELECT 'tag:' || tag FROM rfid_items WHERE id = ?1 AND tag IS NOT NULL",
+                    params![item_id],
+                    |row| row.get(0),
+                )
+            } else {
+                Ok(None)
+            };
+            match linked {
+                Ok(Some(linked)) if !keys.contains(&linked) => keys.push(linked),
+                Ok(_) | Err(rusqlite::Error::QueryReturnedNoRows) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(keys)
+    }
+
+    // Cada chave (tag ou peça) recebe a etapa da operação e a geração do ciclo
+    // de vida. Uma etapa menor que a última da geração atual (nova distribuição
+    // depois da recepção, nova associação depois da baixa) abre a geração
+    // seguinte e espera a anterior terminar: dentro de uma geração as etapas
+    // só crescem, então a ordem final segue a ordem de enfileiramento.
+    fn record_operation_dependencies(
+        conn: &Connection,
+        id: i64,
+        operation: &Operation,
+    ) -> SqlResult<()> {
+        let Some(stage) = operation.stage() else {
+            return Ok(());
+        };
+
+        for key in Self::linked_dependency_keys(conn, operation)? {
+            let latest = conn.query_row(
+                "SELECT generation, MAX(stage) FROM operation_dependencies
+                 WHERE dep_key = ?1
+                 GROUP BY generation ORDER BY generation DESC LIMIT 1",
+                params![key],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, u8>(1)?)),
+            );
+            let generation = match latest {
+                Ok((generation, last_stage)) if last_stage == TERMINAL_STAGE || stage < last_stage => {
+                    generation + 1
+                }
+                Ok((generation, _)) => generation,
+                Err(rusqlite::Error::QueryReturnedNoRows) => 0,
+                Err(e) => return Err(e),
+            };
+            conn.execute(
+                "INSERT OR IGNORE INTO operation_dependencies
+                 (operation_id, dep_key, generation, stage) VALUES (?1, ?2, ?3, ?4)",
+                params![id, key, generation, stage],
+            )?;
+        }
+        Ok(())
+    }
+
+    // A chave de idempotência é gerada uma única vez, aqui, e acompanha a
+    // operação em todas as tentativas (inclusive após reinício do totem).
+    pub fn queue_operation(&self, operation: &Operation) -> SqlResult<QueuedOperation> {
         let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
         let now = chrono::Utc::now().timestamp();
-        
-        conn.execute(
-            "INSERT INTO sync_log (entity, last_sync_at, last_sync_count)
-             VALUES (?1, ?2, ?3)
-             ON CONFLICT(entity) DO UPDATE SET
-                last_sync_at = excluded.last_sync_at,
-                last_sync_count = excluded.last_sync_count",
-            params![entity, now, count],
+
+        if let Some(coalesced) = Self::coalesce_operation(&tx, operation)? {
+            tx.commit()?;
+            return Ok(coalesced);
+        }
+
+        let idempotency_key = Self::new_idempotency_key();
+        tx.execute(
+            "INSERT INTO pending_operations
+             (operation_type, payload, payload_version, created_at, next_attempt_at,
+              idempotency_key) 
+             VALUES (?1, ?2, ?3, ?4, ?4, ?5)",
+            params![
+                operation.operation_type(),
+                operation.payload_json(),
+                PAYLOAD_SCHEMA_VERSION,
+                now,
+                idempotency_key
+            ],
         )?;
+        let id = tx.last_insert_rowid();
+        Self::record_operation_dependencies(&tx, id, operation)?;
+        Self::project_operation(&tx, id, operation)?;
         
-        Ok(())
+        tx.commit()?;
+        Ok(QueuedOperation {
+            id,
+            idempotency_key,
+            outcome: QueueOutcome::Queued,
+        })
     }
 
-    // ==================== STATS ====================
-    
-    pub fn get_stats(&self) -> SqlResult<serde_json::Value> {
+    // Procura uma operação equivalente que ainda não saiu do totem (nunca
+    // reservada para envio) e aplica as regras de Operation::coalesce. Ela
+    // precisa ser a última da fila em todas as tags/peças da nova: se outra
+    // operação veio depois (ex.: recepção → distribuição → recepção), agrupar
+    // apagaria o segundo ciclo.
+    fn coalesce_operation(
+        conn: &Connection,
+        operation: &Operation,
+    ) -> SqlResult<Option<QueuedOperation>> {
+        let keys = Self::linked_dependency_keys(conn, operation)?;
+        let candidates: Vec<(i64, String, String)> = {
+            let mut stmt = conn.prepare(
+                "SELECT id, payload, idempotency_key FROM pending_operations
+                 WHERE operation_type = ?1
+                   AND attempted = 0
+                   AND lease_token IS NULL
+                 ORDER BY id ASC",
+            )?;
+            let rows = stmt.query_map(params![operation.operation_type()], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+            rows.collect::<SqlResult<_>>()?
+        };
+
+        for (id, payload, idempotency_key) in candidates {
+            if Self::has_later_dependent(conn, id, &keys)? {
+                continue;
+            }
+            let Ok(existing) = Operation::parse(operation.operation_type(), &payload) else {
+                continue;
+            };
+            let outcome = match existing.coalesce(operation) {
+                None => continue,
+                Some(Coalesce::Drop) => QueueOutcome::Dropped,
+                Some(Coalesce::Merge(merged)) => {
+                    conn.execute(
+                        "UPDATE pending_operations SET payload = ?2, payload_version = ?3
+                         WHERE id = ?1",
+                        params![id, merged.payload_json(), PAYLOAD_SCHEMA_VERSION],
+                    )?;
+                    Self::record_operation_dependencies(conn, id, &merged)?;
+                    Self::project_operation(conn, id, &merged)?;
+                    QueueOutcome::Merged
+                }
+            };
+            return Ok(Some(QueuedOperation {
+                id,
+                idempotency_key,
+                outcome,
+            }));
+        }
+        Ok(None)
+    }
+
+    fn has_later_dependent(conn: &Connection, id: i64, keys: &[String]) -> SqlResult<bool> {
+        for key in keys {
+            let later: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM operation_dependencies
+                               WHERE dep_key = ?1 AND operation_id > ?2)",
+                params![key, id],
+                |row| row.get(0),
+            )?;
+            if later {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // Uma operação só sai quando nenhuma predecessora na mesma tag/peça
+    // (geração/etapa anterior ou mesma etapa enfileirada antes) continua na
+    // fila — inclusive em backoff, em envio ou na dead-letter.
+    const NOT_BLOCKED_BY_PREDECESSOR: &'static str =
+        "NOT EXISTS (
+           SELECT 1 FROM operation_dependencies d
+           JOIN operation_dependencies p
+             ON p.dep_key = d.dep_key AND p.operation_id != d.operation_id
+           WHERE d.operation_id = pending_operations.id
+             AND (p.generation, p.stage, p.operation_id) < (d.generation, d.stage, d.operation_id)
+         )";
+
+    const PENDING_OPERATION_COLUMNS: &'static str =
+        "id, operation_type, payload, payload_version, created_at, retry_count, last_error,
+         next_attempt_at, idempotency_key, lease_token, lease_expires_at";
+
+    fn row_to_pending_operation(row: &rusqlite::Row) -> SqlResult<PendingOperation> {
+        Ok(PendingOperation {
+            id: row.get(0)?,
+            operation_type: row.get(1)?,
+            payload: row.get(2)?,
+            payload_version: row.get(3)?,
+            created_at: row.get(4)?,
+            retry_count: row.get(5)?,
+            last_error: row.get(6)?,
+            next_attempt_at: row.get(7)?,
+            idempotency_key: row.get(8)?,
+            lease_token: row.get(9)?,
+            lease_expires_at: row.get(10)?,
+        })
+    }
+
+    // Operação pronta para envio em `now`: backoff vencido, sem lease ativo e
+    // sem predecessora na fila
+    fn due_filter(now: &str) -> String {
+        format!(
+            "next_attempt_at <= {now}
+             AND (lease_expires_at IS NULL OR lease_expires_at <= {now})
+             AND {}",
+            Self::NOT_BLOCKED_BY_PREDECESSOR
+        )
+    }
+
+    // Operações cuja próxima tentativa já venceu, na ordem em que o loop de
+    // envio as reservaria. Só leitura: não reserva nada (ver claim_operations).
+    pub fn get_due_operations(&self, limit: usize, now: i64) -> SqlResult<Vec<PendingOperation>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM pending_operations
+             WHERE {}
+             ORDER BY created_at ASC, id ASC
+             LIMIT ?1",
+            Self::PENDING_OPERATION_COLUMNS,
+            Self::due_filter("?2")
+        ))?;
+
+        let ops = stmt.query_map(params![limit, now], Self::row_to_pending_operation)?;
+        ops.collect()
+    }
+
+    pub fn get_pending_operations(&self, limit: usize) -> SqlResult<Vec<PendingOperation>> {
         let conn = self.conn.lock().unwrap();
         
-        let rfid_count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM rfid_items",
-            [],
-            |row| row.get(0),
-        )?;
-        
-        let pending_count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM pending_operations",
-            [],
-            |row| row.get(0),
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM pending_operations
+             ORDER BY created_at ASC
+             LIMIT ?1",
+            Self::PENDING_OPERATION_COLUMNS
+        ))?;
         
-        let last_sync: i64 = conn.query_row(
-            "SELECT MAX(last_sync_at) FROM sync_log",
-            [],
-            |row| row.get(0),
-        ).unwrap_or(0);
+        let ops = stmt.query_map(params![limit], Self::row_to_pending_operation)?;
         
-        Ok(serde_json::json!({
-            "rfid_items_cached": rfid_count,
-            "pending_operations": pending_count,
-            "last_sync_timestamp": last_sync,
-        }))
+        ops.collect()
+    }
+
+    pub fn get_backoff_policy(&self) -> SqlResult<BackoffPolicy> {
+        Ok(self.get_config_as(BACKOFF_POLICY_KEY)?.unwrap_or_default())
     }
-}
 
+    pub fn set_backoff_policy(&self, policy: &BackoffPolicy) -> SqlResult<()> {
+        self.set_config_as(BACKOFF_POLICY_KEY, policy)?;
+        Ok(())
+    }
+
+    pub fn get_sync_config(&self) -> SqlResult<SyncConfig> {
+        Ok(self.get_config_as(SYNC_CONFIG_KEY)?.unwrap_or_default())
+    }
+
+    pub fn set_sync_config(&self, config: &SyncConfig) -> SqlResult<()> {
+        self.set_config_as(SYNC_CONFIG_KEY, config)?;
+        Ok(())
+    }
+
+    // `permanent`: a falha não se resolve com nova tentativa e a operação vai
+    // direto para a dead-letter
+    fn record_failure(
+        conn: &Connection,
+        id: i64,
+        error: &str,
+        policy: &BackoffPolicy,
+        now: i64,
+        permanent: bool,
+    ) -> SqlResult<bool> {
+        let retry_count = match conn.query_row(
+            "SELECT retry_count FROM pending_operations WHERE id = ?1",
+            params![id],
+            |row| row.get::<_, i32>(0),
+        ) {
+            Ok(count) => count + 1,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        
+        conn.execute(
+            "UPDATE pending_operations 
+             SET retry_count = ?4, last_error = ?2, last_retry_at = ?3, next_attempt_at = ?5,
+                 lease_token = NULL, lease_expires_at = NULL
+             WHERE id = ?1",
+            params![id, error, now, retry_count, policy.next_attempt_at(now, retry_count)],
+        )?;
+        conn.execute(
+            "INSERT INTO operation_errors (operation_id, error, attempted_at) VALUES (?1, ?2, ?3)",
+            params![id, error, now],
+        )?;
+
+        let dead = permanent || retry_count >= MAX_OPERATION_RETRIES;
+        if dead {
+            Self::move_to_dead_letter(conn, id, now)?;
+        }
+        Ok(dead)
+    }
+
+    // ==================== LEASES ====================
+
+    // Reserva atomicamente até `limit` operações prontas para envio. Cada
+    // operação reservada fica invisível para outros loops até `lease_secs`
+    // vencer; depois disso volta sozinha para a fila.
+    pub fn claim_operations(&self, limit: usize, lease_secs: i64) -> SqlResult<Vec<PendingOperation>> {
+        self.claim_operations_at(limit, lease_secs, chrono::Utc::now().timestamp())
+    }
+
+    pub fn claim_operations_at(
+        &self,
+        limit: usize,
+        lease_secs: i64,
+        now: i64,
+    ) -> SqlResult<Vec<PendingOperation>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        let lease_token = uuid::Uuid::new_v4().to_string();
+
+        tx.execute(
+            &format!(
+                "UPDATE pending_operations
+                 SET lease_token = ?1, lease_expires_at = ?2, attempted = 1
+                 WHERE id IN (
+                   SELECT id FROM pending_operations
+                   WHERE {}
+                   ORDER BY created_at ASC, id ASC
+                   LIMIT ?4
+                 )",
+                Self::due_filter("?3")
+            ),
+            params![lease_token, now + lease_secs.max(1), now, limit],
+        )?;
+
+        let ops = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT {} FROM pending_operations
+                 WHERE lease_token = ?1
+                 ORDER BY created_at ASC, id ASC",
+                Self::PENDING_OPERATION_COLUMNS
+            ))?;
+            let ops = stmt.query_map(params![lease_token], Self::row_to_pending_operation)?;
+            ops.collect::<SqlResult<Vec<_>>>()?
+        };
+
+        tx.commit()?;
+        Ok(ops)
+    }
+
+    // Envio confirmado: remove a operação. Retorna false se o lease não é mais
+    // deste chamador (venceu e foi reservado por outro loop).
+    pub fn complete_operation(&self, id: i64, lease_token: &str) -> SqlResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        let deleted = tx.execute(
+            "DELETE FROM pending_operations WHERE id = ?1 AND lease_token = ?2",
+            params![id, lease_token],
+        )?;
+        if deleted > 0 {
+            tx.execute("DELETE FROM operation_errors WHERE operation_id = ?1", params![id])?;
+            tx.execute("DELETE FROM operation_dependencies WHERE operation_id = ?1", params![id])?;
+            Self::settle_projection(&tx, id)?;
+        }
+
+        tx.commit()?;
+        Ok(deleted > 0)
+    }
+
+    // Envio falhou: libera o lease, agenda a próxima tentativa (backoff) e,
+    // se esgotou as tentativas, move para a dead-letter. Retorna true nesse caso.
+    pub fn fail_operation(&self, id: i64, lease_token: &str, error: &str) -> SqlResult<bool> {
+        self.record_attempt_failure(id, lease_token, error, false)
+    }
+
+    // Operação que nunca vai ser aceita (ex.: payload legado que não passa na
+    // validação): registra o erro e move direto para a dead-letter. Retorna
+    // false se o lease não é mais deste chamador.
+    pub fn dead_letter_operation(&self, id: i64, lease_token: &str, error: &str) -> SqlResult<bool> {
+        self.record_attempt_failure(id, lease_token, error, true)
+    }
+
+    fn record_attempt_failure(
+        &self,
+        id: i64,
+        lease_token: &str,
+        error: &str,
+        permanent: bool,
+    ) -> SqlResult<bool> {
+        let policy = self.get_backoff_policy()?;
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        let now = chrono::Utc::now().timestamp();
+
+        let owned: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM pending_operations WHERE id = ?1 AND lease_token = ?2)",
+            params![id, lease_token],
+            |row| row.get(0),
+        )?;
+        if !owned {
+            return Ok(false);
+        }
+
+        let dead = Self::record_failure(&tx, id, error, &policy, now, permanent)?;
+
+        tx.commit()?;
+        Ok(dead)
+    }
+
+    // Devolve operações reservadas sem registrar falha (ex.: loop interrompido)
+    pub fn release_operations(&self, lease_token: &str) -> SqlResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE pending_operations SET lease_token = NULL, lease_expires_at = NULL
+             WHERE lease_token = ?1",
+            params![lease_token],
+        )
+    }
+
+    // Reserva só a operação `id` (se ninguém mais está com ela) para os
+    // comandos sem lease abaixo
+    fn lease_operation(&self, id: i64) -> SqlResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let lease_token = uuid::Uuid::new_v4().to_string();
+
+        let leased = conn.execute(
+            "UPDATE pending_operations
+             SET lease_token = ?1, lease_expires_at = ?2, attempted = 1
+             WHERE id = ?3 AND (lease_expires_at IS NULL OR lease_expires_at <= ?4)",
+            params![lease_token, now + 60, id, now],
+        )?;
+        Ok((leased > 0).then_some(lease_token))
+    }
+
+    // Fluxo antigo (envio pela webview, sem lease): equivale a reservar e
+    // chamar complete_operation. Retorna false se a operação não existe ou
+    // está reservada por outro loop.
+    pub fn delete_operation(&self, id: i64) -> SqlResult<bool> {
+        match self.lease_operation(id)? {
+            Some(lease_token) => self.complete_operation(id, &lease_token),
+            None => Ok(false),
+        }
+    }
+
+    // Fluxo antigo: equivale a reservar e chamar fail_operation. Retorna true
+    // se a operação foi para a dead-letter; false também quando outro loop
+    // está com ela (nada é registrado).
+    pub fn increment_retry_count(&self, id: i64, error: &str) -> SqlResult<bool> {
+        match self.lease_operation(id)? {
+            Some(lease_token) => self.fail_operation(id, &lease_token, error),
+            None => Ok(false),
+        }
+    }
+
+    fn move_to_dead_letter(conn: &Connection, id: i64, now: i64) -> SqlResult<()> {
+        conn.execute(
+            "INSERT OR REPLACE INTO failed_operations
+             (id, operation_type, payload, payload_version, created_at, retry_count, last_error,
+              failed_at, idempotency_key)
+             SELECT id, operation_type, payload, payload_version, created_at, retry_count,
+                    last_error, ?2, idempotency_key
+             FROM pending_operations WHERE id = ?1",
+            params![id, now],
+        )?;
+        conn.execute("DELETE FROM pending_operations WHERE id = ?1", params![id])?;
+        // Falha definitiva: a peça volta a mostrar o status do servidor
+        Self::discard_projection(conn, id)
+
+    }
+
+    pub fn get_pending_count(&self) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM pending_operations",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count)
+    }
+
+    // ==================== DEAD LETTER ====================
+
+    const FAILED_OPERATION_COLUMNS: &'static str =
+        "id, operation_type, payload, payload_version, created_at, retry_count, last_error, failed_at,
+         idempotency_key";
+
+    fn row_to_failed_operation(row: &rusqlite::Row) -> SqlResult<FailedOperation> {
+        Ok(FailedOperation {
+            id: row.get(0)?,
+            operation_type: row.get(1)?,
+            payload: row.get(2)?,
+            payload_version: row.get(3)?,
+            created_at: row.get(4)?,
+            retry_count: row.get(5)?,
+            last_error: row.get(6)?,
+            failed_at: row.get(7)?,
+            idempotency_key: row.get(8)?,
+        })
+    }
+
+    pub fn list_failed_operations(&self, limit: usize) -> SqlResult<Vec<FailedOperation>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM failed_operations ORDER BY failed_at DESC LIMIT ?1",
+            Self::FAILED_OPERATION_COLUMNS
+        ))?;
+
+        let ops = stmt.query_map(params![limit], Self::row_to_failed_operation)?;
+        ops.collect()
+    }
+
+    pub fn get_failed_operation(&self, id: i64) -> SqlResult<Option<FailedOperationDetail>> {
+        let conn = self.conn.lock().unwrap();
+
+        let result = conn.query_row(
+            &format!("SELECT {} FROM failed_operations WHERE id = ?1", Self::FAILED_OPERATION_COLUMNS),
+            params![id],
+            Self::row_to_failed_operation,
+        );
+        let operation = match result {
+            Ok(op) => op,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut stmt = conn.prepare(
+            "SELECT error, attempted_at FROM operation_errors
+             WHERE operation_id = ?1
+             ORDER BY attempted_at ASC, id ASC"
+        )?;
+        let errors = stmt
+            .query_map(params![id], |row| {
+                Ok(OperationError {
+                    error: row.get(0)?,
+                    attempted_at: row.get(1)?,
+                })
+            })?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        Ok(Some(FailedOperationDetail { operation, errors }))
+    }
+
+    // Devolve a operação à fila (com as tentativas zeradas), opcionalmente com
+    // o payload corrigido. O histórico de erros é mantido.
+    // Um payload corrigido pelo operador passa pela mesma validação do
+    // enqueue, usando o tipo original da operação.
+    pub fn requeue_failed_operation(&self, id: i64, payload: Option<&str>) -> SqlResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        let edited = match payload {
+            Some(payload) => {
+                let operation_type: String = match tx.query_row(
+                    "SELECT operation_type FROM failed_operations WHERE id = ?1",
+                    params![id],
+                    |row| row.get(0),
+                ) {
+                    Ok(operation_type) => operation_type,
+                    Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(false),
+                    Err(e) => return Err(e),
+                };
+                Some(Self::parse_operation(&operation_type, payload)?)
+            }
+            None => None,
+        };
+        let payload = edited.as_ref().map(Operation::payload_json);
+        let payload_version = edited.as_ref().map(|_| PAYLOAD_SCHEMA_VERSION);
+
+        tx.execute(
+            "INSERT INTO pending_operations
+             (id, operation_type, payload, payload_version, created_at, retry_count, last_error,
+              next_attempt_at, idempotency_key, attempted)
+             SELECT id, operation_type, COALESCE(?2, payload), COALESCE(?3, payload_version),
+                    created_at, 0, last_error, ?4, idempotency_key, 1
+             FROM failed_operations WHERE id = ?1",
+            params![id, payload, payload_version, chrono::Utc::now().timestamp()],
+        )?;
+        if tx.changes() == 0 {
+            return Ok(false);
+        }
+        tx.execute("DELETE FROM failed_operations WHERE id = ?1", params![id])?;
+        // O payload corrigido pode tocar outras tags: refaz as dependências
+        if let Some(operation) = &edited {
+            tx.execute("DELETE FROM operation_dependencies WHERE operation_id = ?1", params![id])?;
+            Self::record_operation_dependencies(&tx, id, operation)?;
+        }
+        // De volta à fila, volta a ser projetada sobre o cache
+        let (operation_type, payload): (String, String) = tx.query_row(
+            "SELECT operation_type, payload FROM pending_operations WHERE id = ?1",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        if let Ok(operation) = Operation::parse(&operation_type, &payload) {
+            Self::project_operation(&tx, id, &operation)?;
+        }
+
+        tx.commit()?;
+        Ok(true)
+    }
+
+    pub fn discard_failed_operation(&self, id: i64) -> SqlResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        let deleted = tx.execute("DELETE FROM failed_operations WHERE id = ?1", params![id])?;
+        tx.execute("DELETE FROM operation_errors WHERE operation_id = ?1", params![id])?;
+        tx.execute("DELETE FROM operation_dependencies WHERE operation_id = ?1", params![id])?;
+
+        tx.commit()?;
+        Ok(deleted > 0)
+    }
+
+    pub fn get_failed_count(&self) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT COUNT(*) FROM failed_operations", [], |row| row.get(0))
+    }
+
+    // ==================== SYNC LOG ====================
+    
+    pub fn get_last_sync(&self, entity: &str) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        
+        let result = conn.query_row(
+            "SELECT last_sync_at FROM sync_log WHERE entity = ?1",
+            params![entity],
+            |row| row.get(0),
+        );
+        
+        match result {
+            Ok(timestamp) => Ok(timestamp),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn update_sync_log(&self, entity: &str, count: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        Self::record_sync(&conn, entity, count, None, None)
+    }
+
+    pub const RFID_SYNC_ENTITY: &'static str = "rfid_items";
+
+    pub fn get_sync_cursor(&self, entity: &str) -> SqlResult<SyncCursor> {
+        let conn = self.conn.lock().unwrap();
+
+        let result = conn.query_row(
+            "SELECT cursor, high_water_mark, last_sync_at FROM sync_log WHERE entity = ?1",
+            params![entity],
+            |row| {
+                Ok(SyncCursor {
+                    cursor: row.get(0)?,
+                    high_water_mark: row.get(1)?,
+                    last_sync_at: row.get(2)?,
+                })
+            },
+        );
+
+        match result {
+            Ok(cursor) => Ok(cursor),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(SyncCursor::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // O cursor do servidor substitui o anterior; o high-water mark só avança.
+    // Nenhum dos dois vem do relógio local.
+    fn record_sync(
+        conn: &Connection,
+        entity: &str,
+        count: i64,
+        cursor: Option<&str>,
+        high_water_mark: Option<i64>,
+    ) -> SqlResult<()> {
+        let now = chrono::Utc::now().timestamp();
+
+        conn.execute(
+            "INSERT INTO sync_log (entity, last_sync_at, last_sync_count, cursor, high_water_mark)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(entity) DO UPDATE SET
+                last_sync_at = excluded.last_sync_at,
+                last_sync_count = excluded.last_sync_count,
+                cursor = COALESCE(excluded.cursor, sync_log.cursor),
+                high_water_mark = MAX(
+                    COALESCE(excluded.high_water_mark, sync_log.high_water_mark),
+                    COALESCE(sync_log.high_water_mark, excluded.high_water_mark)
+                )",
+            params![entity, now, count, cursor, high_water_mark],
+        )?;
+
+        Ok(())
+    }
+
+    // ==================== LOCAL CONFIG ====================
+
+    // Chaves com estes prefixos são do próprio Rust (marcadores e a config do
+    // sync, que inclui a api_key): a webview não lê, grava nem lista
+    const CONFIG_INTERNAL_PREFIXES: [&'static str; 2] = ["_meta:", "sync:"];
+    const CONFIG_IMPORTED_KEY: &'static str = "_meta:local_storage_imported";
+
+    pub fn is_internal_config_key(key: &str) -> bool {
+        Self::CONFIG_INTERNAL_PREFIXES.iter().any(|prefix| key.starts_with(prefix))
+    }
+
+    fn row_to_config_entry(row: &rusqlite::Row) -> SqlResult<ConfigEntry> {
+        let raw: String = row.get(1)?;
+        let value = serde_json::from_str(&raw).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e))
+        })?;
+        Ok(ConfigEntry {
+            key: row.get(0)?,
+            value,
+            updated_at: row.get(2)?,
+        })
+    }
+
+    fn write_config(conn: &Connection, key: &str, value: &JsonValue, now: i64) -> SqlResult<()> {
+        conn.execute(
+            "INSERT INTO local_config (key, value, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(key) DO UPDATE SET
+                value = excluded.value,
+                updated_at = excluded.updated_at",
+            params![key, value.to_string(), now],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_config(&self, key: &str) -> SqlResult<Option<ConfigEntry>> {
+        let conn = self.conn.lock().unwrap();
+
+        let result = conn.query_row(
+            "SELECT key, value, updated_at FROM local_config WHERE key = ?1",
+            params![key],
+            Self::row_to_config_entry,
+        );
+
+        match result {
+            Ok(entry) => Ok(Some(entry)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn get_config_as<T: DeserializeOwned>(&self, key: &str) -> SqlResult<Option<T>> {
+        match self.get_config(key)? {
+            Some(entry) => serde_json::from_value(entry.value).map(Some).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_config(&self, key: &str, value: &JsonValue) -> SqlResult<ConfigEntry> {
+        let conn = self.conn.lock().unwrap();
+        let now = chrono::Utc::now().timestamp();
+
+        Self::write_config(&conn, key, value, now)?;
+
+        Ok(ConfigEntry {
+            key: key.to_string(),
+            value: value.clone(),
+            updated_at: now,
+        })
+    }
+
+    pub fn set_config_as<T: Serialize>(&self, key: &str, value: &T) -> SqlResult<ConfigEntry> {
+        let value = serde_json::to_value(value)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        self.set_config(key, &value)
+    }
+
+    pub fn delete_config(&self, key: &str) -> SqlResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute("DELETE FROM local_config WHERE key = ?1", params![key])?;
+        Ok(deleted > 0)
+    }
+
+    pub fn list_config(&self) -> SqlResult<Vec<ConfigEntry>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT key, value, updated_at FROM local_config ORDER BY key ASC"
+        )?;
+
+        let mut entries = Vec::new();
+        for entry in stmt.query_map([], Self::row_to_config_entry)? {
+            let entry = entry?;
+            if !Self::is_internal_config_key(&entry.key) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    // Importação única dos valores que viviam no localStorage da webview.
+    // Não sobrescreve chaves já gravadas e, depois de executada, vira no-op.
+    pub fn import_config(&self, entries: &[(String, JsonValue)]) -> SqlResult<Vec<ConfigEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        let now = chrono::Utc::now().timestamp();
+
+        let already_imported: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM local_config WHERE key = ?1)",
+            params![Self::CONFIG_IMPORTED_KEY],
+            |row| row.get(0),
+        )?;
+        if already_imported {
+            return Ok(Vec::new());
+        }
+
+        let mut imported = Vec::new();
+        for (key, value) in entries {
+            if Self::is_internal_config_key(key) {
+                continue;
+            }
+            let inserted = tx.execute(
+                "INSERT OR IGNORE INTO local_config (key, value, updated_at) VALUES (?1, ?2, ?3)",
+                params![key, value.to_string(), now],
+            )?;
+            if inserted > 0 {
+                imported.push(ConfigEntry {
+                    key: key.clone(),
+                    value: value.clone(),
+                    updated_at: now,
+                });
+            }
+        }
+
+        Self::write_config(&tx, Self::CONFIG_IMPORTED_KEY, &JsonValue::Bool(true), now)?;
+        tx.commit()?;
+        Ok(imported)
+    }
+
+    // ==================== STATS ====================
+    
+    pub fn get_stats(&self) -> SqlResult<serde_json::Value> {
+        let conn = self.conn.lock().unwrap();
+        
+        let rfid_count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM rfid_items",
+            [],
+            |row| row.get(0),
+        )?;
+        
+        let linen_count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM linen_items",
+            [],
+            |row| row.get(0),
+        )?;
+        
+        let sector_count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sectors",
+            [],
+            |row| row.get(0),
+        )?;
+        
+        let bed_count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM beds",
+            [],
+            |row| row.get(0),
+        )?;
+        
+        let pending_count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM pending_operations",
+            [],
+            |row| row.get(0),
+        )?;
+        
+        let failed_count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM failed_operations",
+            [],
+            |row| row.get(0),
+        )?;
+        
+        let last_sync: i64 = conn.query_row(
+            "SELECT MAX(last_sync_at) FROM sync_log",
+            [],
+            |row| row.get(0),
+        ).unwrap_or(0);
+        
+        Ok(serde_json::json!({
+            "rfid_items_cached": rfid_count,
+            "linen_items_cached": linen_count,
+            "sectors_cached": sector_count,
+            "beds_cached": bed_count,
+            "pending_operations": pending_count,
+            "dead_letter_operations": failed_count,
+            "last_sync_timestamp": last_sync,
+        }))
+    }
+}
+
+// Banco temporário para os testes: o arquivo é apagado no Drop, inclusive
+// quando o teste falha no meio
+#[cfg(test)]
+pub(crate) struct TempDb {
+    path: PathBuf,
+    db: Option<Database>,
+}
+
+#[cfg(test)]
+impl TempDb {
+    pub fn new(name: &str) -> Self {
+        let mut temp = Self::empty(name);
+        temp.reopen();
+        temp
+    }
+
+    // Só reserva o caminho, para o teste montar o arquivo antes de abrir
+    pub fn empty(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "ecolav-test-{}-{}-{}.db",
+            name,
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let _ = std::fs::remove_file(&path);
+        Self { path, db: None }
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    // Fecha e abre de novo o mesmo arquivo, como num reinício do totem
+    pub fn reopen(&mut self) {
+        self.db = None;
+        self.db = Some(Database::new(self.path.clone()).unwrap());
+    }
+
+    pub fn close(&mut self) {
+        self.db = None;
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempDb {
+    type Target = Database;
+
+    fn deref(&self) -> &Database {
+        self.db.as_ref().expect("banco de teste fechado")
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        self.db = None;
+        for suffix in ["", "-wal", "-shm", "-journal"] {
+            let _ = std::fs::remove_file(format!("{}{}", self.path.display(), suffix));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upgrades_v1_database_preserving_data() {
+        let mut db = TempDb::empty("upgrade");
+
+        // Banco criado por versões antigas: só o 001, sem schema_version
+        {
+            let conn = Connection::open(db.path()).unwrap();
+            let Migration::Sql(initial) = MIGRATIONS[0].1 else {
+                unreachable!()
+            };
+            conn.execute_batch(initial).unwrap();
+            conn.execute(
+                "INSERT INTO rfid_items (id, tag, tid, updated_at)
+                 VALUES ('p1', 'e2 00ab cd', 'e280-0001', 10),
+                        ('old', 'E200ABCD', NULL, 5)",
+                [],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO pending_operations (operation_type, payload, created_at)
+                 VALUES ('distribute', '{}', 20)",
+                [],
+            )
+            .unwrap();
+        }
+
+        db.reopen();
+        {
+            let conn = db.conn.lock().unwrap();
+            assert_eq!(Database::schema_version(&conn).unwrap(), SCHEMA_VERSION);
+        }
+        let item = db.lookup_rfid_item("e200abcd").unwrap().unwrap();
+        assert_eq!(item.id, "p1");
+        assert_eq!(item.tag, "E200ABCD");
+        assert_eq!(item.tid.as_deref(), Some("E2800001"));
+        assert_eq!(db.get_stats().unwrap()["rfid_items_cached"], 1);
+        assert_eq!(db.get_pending_count().unwrap(), 1);
+        let found = db
+            .search_rfid_items(&RfidSearchQuery {
+                text: "e200ab".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(found.items.iter().map(|i| i.id.as_str()).collect::<Vec<_>>(), vec!["p1"]);
+
+        // Reabrir não reaplica nada
+        db.reopen();
+        assert_eq!(db.get_pending_count().unwrap(), 1);
+    }
+
+    fn rfid_item(id: &str, tag: &str, tid: Option<&str>, updated_at: i64) -> RfidItem {
+        RfidItem {
+            id: id.to_string(),
+            tag: tag.to_string(),
+            tid: tid.map(str::to_string),
+            linen_item_id: None,
+            linen_item_name: None,
+            linen_item_sku: None,
+            full_number: None,
+            batch_number: None,
+            piece_number: None,
+            status: Some("EM_USO".to_string()),
+            client_id: None,
+            client_name: None,
+            updated_at,
+            pending_sync: false,
+        }
+    }
+
+    #[test]
+    fn bulk_lookup_keys_by_input_and_reports_unknown() {
+        let db = TempDb::new("bulk");
+        db.bulk_upsert_rfid_items(&[
+            rfid_item("p1", "E200AA01", Some("E280A001"), 1),
+            rfid_item("p2", "E200AA02", None, 1),
+        ])
+        .unwrap();
+
+        let tags = vec![
+            "e200 aa01".to_string(),
+            "E280A001".to_string(),
+            "E200AA02".to_string(),
+            "E200FFFF".to_string(),
+            "E200AA02".to_string(),
+        ];
+        let result = db.lookup_rfid_items_bulk(&tags).unwrap();
+
+        assert_eq!(result.found.len(), 3);
+        assert_eq!(result.found["e200 aa01"].id, "p1");
+        assert_eq!(result.found["E280A001"].id, "p1");
+        assert_eq!(result.found["E200AA02"].id, "p2");
+        assert_eq!(result.unknown, vec!["E200FFFF".to_string()]);
+    }
+
+    // cargo test --release bench_bulk_lookup -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_bulk_lookup_5k_tags_against_200k_rows() {
+        let db = TempDb::new("bench");
+
+        let items: Vec<RfidItem> = (0..200_000)
+            .map(|i| rfid_item(&format!("p{}", i), &format!("E200{:020X}", i), None, i))
+            .collect();
+        db.bulk_upsert_rfid_items(&items).unwrap();
+
+        // Metade conhecidas, metade desconhecidas
+        let tags: Vec<String> = (0..5_000)
+            .map(|i| {
+                if i % 2 == 0 {
+                    format!("E200{:020X}", i * 37)
+                } else {
+                    format!("E300{:020X}", i)
+                }
+            })
+            .collect();
+
+        let start = std::time::Instant::now();
+        let result = db.lookup_rfid_items_bulk(&tags).unwrap();
+        let elapsed = start.elapsed();
+
+        assert_eq!(result.found.len(), 2_500);
+        assert_eq!(result.unknown.len(), 2_500);
+        println!("lookup_rfid_items_bulk: 5000 tags / 200k linhas em {:?}", elapsed);
+    }
+
+    fn search(db: &Database, text: &str, configure: impl FnOnce(&mut RfidSearchQuery)) -> Vec<String> {
+        let mut query = RfidSearchQuery {
+            text: text.to_string(),
+            ..Default::default()
+        };
+        configure(&mut query);
+        let result = db.search_rfid_items(&query).unwrap();
+        result.items.into_iter().map(|item| item.id).collect()
+    }
+
+    fn searchable_item(id: &str, tag: &str, full_number: &str, name: &str, client: &str) -> RfidItem {
+        RfidItem {
+            full_number: Some(full_number.to_string()),
+            linen_item_name: Some(name.to_string()),
+            client_id: Some(client.to_string()),
+            ..rfid_item(id, tag, None, 10)
+        }
+    }
+
+    #[test]
+    fn search_matches_prefixes_without_accents_and_ranks_printed_number_first() {
+        let db = TempDb::new("search");
+        db.bulk_upsert_rfid_items(&[
+            searchable_item("lencol", "E200AA01", "LC-000123", "Lençol Hospitalar", "c1"),
+            searchable_item("fronha", "E200AA02", "FR-000777", "Fronha 000123", "c1"),
+            searchable_item("toalha", "E200BB01", "TB-000124", "Toalha de Banho", "c2"),
+        ])
+        .unwrap();
+
+        assert_eq!(search(&db, "lencol", |_| {}), vec!["lencol"]);
+        assert_eq!(search(&db, "LENÇ hosp", |_| {}), vec!["lencol"]);
+        assert_eq!(search(&db, "e200aa", |_| {}).len(), 2);
+        assert!(search(&db, "E200CC", |_| {}).is_empty());
+        assert!(search(&db, " -\"\"* ", |_| {}).is_empty());
+
+        // Número impresso pesa mais que o nome do produto
+        assert_eq!(search(&db, "000123", |_| {}), vec!["lencol", "fronha"]);
+        assert_eq!(search(&db, "00012", |_| {}).len(), 3);
+        assert_eq!(search(&db, "00012", |q| q.client_id = Some("c2".into())), vec!["toalha"]);
+
+        // Peça desanexada sai da busca; a tag reaproveitada aparece na nova peça
+        db.replace_rfid_tag("lencol", "E200AA09", None, 20).unwrap();
+        assert!(search(&db, "E200AA01", |_| {}).is_empty());
+        assert_eq!(search(&db, "E200AA09", |_| {}), vec!["lencol"]);
+    }
+
+    #[test]
+    fn search_filters_by_projected_status_and_paginates() {
+        let db = TempDb::new("search-filters");
+        let items: Vec<RfidItem> = (0..5)
+            .map(|i| {
+                searchable_item(&format!("p{}", i), &format!("E200AA0{}", i), "LC-1", "Lençol", "c1")
+            })
+            .collect();
+        db.bulk_upsert_rfid_items(&items).unwrap();
+
+        let page = |offset| {
+            let result = db
+                .search_rfid_items(&RfidSearchQuery {
+                    text: "lencol".to_string(),
+                    limit: Some(2),
+                    offset: Some(offset),
+                    ..Default::default()
+                })
+                .unwrap();
+            (result.total, result.items.len())
+        };
+        assert_eq!((page(0), page(2), page(4), page(6)), ((5, 2), (5, 2), (5, 1), (5, 0)));
+
+        // Distribuição ainda na fila: a peça já aparece como distribuída
+        let distribute =
+            Operation::parse("distribute", r#"{"tags":["E200AA03"],"bedId":"b1"}"#).unwrap();
+        db.queue_operation(&distribute).unwrap();
+        let status = |status: &str| search(&db, "lencol", |q| q.status = Some(status.into()));
+        assert_eq!(status("DISTRIBUIDO"), vec!["p3"]);
+        assert_eq!(status("EM_USO").len(), 4);
+        assert!(!status("EM_USO").contains(&"p3".to_string()));
+    }
+
+    #[test]
+    fn search_index_survives_vacuum_after_deletes() {
+        let db = TempDb::new("search-vacuum");
+        db.bulk_upsert_rfid_items(&[
+            searchable_item("a", "E200AA01", "LC-1", "Lençol", "c1"),
+            searchable_item("b", "E200AA02", "FR-2", "Fronha", "c1"),
+            searchable_item("c", "E200AA03", "TB-3", "Toalha", "c1"),
+        ])
+        .unwrap();
+        db.apply_rfid_sync(&sync_batch(Vec::new(), &[("a", 20)])).unwrap();
+
+        db.conn.lock().unwrap().execute_batch("VACUUM").unwrap();
+        assert_eq!(search(&db, "fronha", |_| {}), vec!["b"]);
+        assert_eq!(search(&db, "toalha", |_| {}), vec!["c"]);
+        assert!(search(&db, "lencol", |_| {}).is_empty());
+    }
+
+    fn tag_moves(db: &Database, tag: &str) -> Vec<(Option<String>, Option<String>)> {
+        let conn = db.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT from_item_id, to_item_id FROM tag_moves WHERE tag = ?1 ORDER BY id")
+            .unwrap();
+        let rows = stmt
+            .query_map(params![tag], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        rows.collect::<SqlResult<_>>().unwrap()
+    }
+
+    #[test]
+    fn sync_moves_tag_to_newer_piece_and_detaches_old_one() {
+        let db = TempDb::new("move");
+        db.upsert_rfid_item(&rfid_item("a", "E200AA01", Some("E280A001"), 10)).unwrap();
+
+        // RFID_REPLACE_TAG no servidor: a tag passou para a peça B
+        let summary = db
+            .bulk_upsert_rfid_items(&[rfid_item("b", "E200AA01", Some("E280A001"), 20)])
+            .unwrap();
+        assert_eq!(summary.upserted, 1);
+
+        assert_eq!(db.lookup_rfid_item("E200AA01").unwrap().unwrap().id, "b");
+        assert_eq!(db.lookup_rfid_item("E280A001").unwrap().unwrap().id, "b");
+        {
+            let conn = db.conn.lock().unwrap();
+            let (tag, detached_at): (Option<String>, Option<i64>) = conn
+                .query_row("SELECT tag, detached_at FROM rfid_items WHERE id = 'a'", [], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .unwrap();
+            assert_eq!(tag, None);
+            assert_eq!(detached_at, Some(20));
+        }
+        assert_eq!(
+            tag_moves(&db, "E200AA01"),
+            vec![(Some("a".to_string()), Some("b".to_string()))]
+        );
+    }
+
+    #[test]
+    fn stale_association_does_not_steal_tag_nor_roll_back_batch() {
+        let db = TempDb::new("stale");
+
+        // Mesmo lote com a associação nova antes da antiga
+        let summary = db
+            .bulk_upsert_rfid_items(&[
+                rfid_item("b", "E200AA01", None, 20),
+                rfid_item("a", "E200AA01", None, 10),
+                rfid_item("c", "E200AA02", None, 5),
+            ])
+            .unwrap();
+        assert_eq!(summary.upserted, 3);
+
+        assert_eq!(db.lookup_rfid_item("E200AA01").unwrap().unwrap().id, "b");
+        assert_eq!(db.lookup_rfid_item("E200AA02").unwrap().unwrap().id, "c");
+        assert!(tag_moves(&db, "E200AA01").is_empty());
+
+        // Atualização antiga da própria peça também não sobrescreve a nova
+        db.upsert_rfid_item(&rfid_item("b", "E200AA03", None, 15)).unwrap();
+        assert_eq!(db.lookup_rfid_item("E200AA01").unwrap().unwrap().id, "b");
+    }
+
+    #[test]
+    fn local_replace_and_detach_tag() {
+        let db = TempDb::new("replace");
+        db.bulk_upsert_rfid_items(&[
+            rfid_item("a", "E200AA01", None, 10),
+            rfid_item("b", "E200AA02", None, 10),
+        ])
+        .unwrap();
+
+        // RFID_REPLACE_TAG: A recebe a tag que estava com B
+        assert!(db.replace_rfid_tag("a", "e200aa02", None, 30).unwrap());
+        assert_eq!(db.lookup_rfid_item("E200AA02").unwrap().unwrap().id, "a");
+        assert!(db.lookup_rfid_item("E200AA01").unwrap().is_none());
+        assert_eq!(
+            tag_moves(&db, "E200AA02"),
+            vec![(Some("b".to_string()), Some("a".to_string()))]
+        );
+        assert_eq!(tag_moves(&db, "E200AA01"), vec![(Some("a".to_string()), None)]);
+
+        // RFID_DETACH_TAG: a tag fica livre
+        assert_eq!(db.detach_rfid_tag("E200AA02", 40).unwrap().as_deref(), Some("a"));
+        assert!(db.lookup_rfid_item("E200AA02").unwrap().is_none());
+        assert_eq!(db.detach_rfid_tag("E200AA02", 50).unwrap(), None);
+
+        // A tag liberada pode ser associada a outra peça sem conflito
+        db.upsert_rfid_item(&rfid_item("c", "E200AA02", None, 60)).unwrap();
+        assert_eq!(db.lookup_rfid_item("E200AA02").unwrap().unwrap().id, "c");
+    }
+
+    type HistoryRow = (String, String, i64, Option<i64>, String, Option<String>);
+
+    // (tag, peça, associada em, desanexada em, origem, origem do desanexo)
+    fn history_rows(entries: Vec<TagHistoryEntry>) -> Vec<HistoryRow> {
+        entries
+            .into_iter()
+            .map(|h| (h.tag, h.item_id, h.associated_at, h.detached_at, h.source, h.detached_source))
+            .collect()
+    }
+
+    fn history_row(
+        tag: &str,
+        item: &str,
+        associated_at: i64,
+        detached: Option<(i64, &str)>,
+        source: &str,
+    ) -> HistoryRow {
+        (
+            tag.to_string(),
+            item.to_string(),
+            associated_at,
+            detached.map(|(at, _)| at),
+            source.to_string(),
+            detached.map(|(_, source)| source.to_string()),
+        )
+    }
+
+    #[test]
+    fn tag_history_follows_associate_replace_detach() {
+        let db = TempDb::new("history");
+
+        // Associação (vinda do servidor), troca de tag e desanexo locais
+        db.upsert_rfid_item(&rfid_item("a", "E200AA01", None, 10)).unwrap();
+        db.upsert_rfid_item(&rfid_item("a", "E200AA01", None, 15)).unwrap();
+        assert!(db.replace_rfid_tag("a", "E200AA02", None, 20).unwrap());
+        assert_eq!(db.detach_rfid_tag("E200AA02", 30).unwrap().as_deref(), Some("a"));
+
+        assert_eq!(
+            history_rows(db.get_item_tag_history("a").unwrap()),
+            vec![
+                history_row("E200AA01", "a", 10, Some((20, "local")), "sync"),
+                history_row("E200AA02", "a", 20, Some((30, "local")), "local"),
+            ]
+        );
+        assert_eq!(
+            history_rows(db.get_tag_history("e2-00-aa-02").unwrap()),
+            vec![history_row("E200AA02", "a", 20, Some((30, "local")), "local")]
+        );
+
+        // A tag antiga é reaproveitada em outra peça: um novo período aberto
+        db.upsert_rfid_item(&rfid_item("b", "E200AA01", None, 40)).unwrap();
+        assert_eq!(
+            history_rows(db.get_tag_history("E200AA01").unwrap()),
+            vec![
+                history_row("E200AA01", "a", 10, Some((20, "local")), "sync"),
+                history_row("E200AA01", "b", 40, None, "sync"),
+            ]
+        );
+        assert_eq!(db.get_item_tag_history("b").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn stale_version_of_a_piece_does_not_take_a_tag_from_another() {
+        let db = TempDb::new("stale-self");
+        db.bulk_upsert_rfid_items(&[
+            rfid_item("x", "E200CC01", None, 200),
+            rfid_item("y", "E200AA01", None, 100),
+        ])
+        .unwrap();
+        let y_history = history_rows(db.get_item_tag_history("y").unwrap());
+
+        // Versão atrasada de x dizendo que ela estava com a tag de y
+        db.bulk_upsert_rfid_items(&[rfid_item("x", "E200AA01", None, 150)]).unwrap();
+
+        assert_eq!(db.lookup_rfid_item("E200AA01").unwrap().unwrap().id, "y");
+        let x = db.lookup_rfid_item("E200CC01").unwrap().unwrap();
+        assert_eq!((x.id.as_str(), x.updated_at), ("x", 200));
+        assert!(tag_moves(&db, "E200AA01").is_empty());
+        assert_eq!(history_rows(db.get_item_tag_history("y").unwrap()), y_history);
+        assert_eq!(history_rows(db.get_item_tag_history("x").unwrap()).len(), 1);
+    }
+
+    #[test]
+    fn replace_onto_tag_of_newer_piece_changes_nothing() {
+        let db = TempDb::new("replace-conflict");
+        db.bulk_upsert_rfid_items(&[
+            rfid_item("a", "E200AA01", Some("E280A001"), 10),
+            rfid_item("b", "E200AA02", None, 100),
+        ])
+        .unwrap();
+        let snapshot = |db: &Database| {
+            (
+                db.lookup_rfid_item("E200AA01").unwrap().map(|i| (i.id, i.updated_at)),
+                db.lookup_rfid_item("E200AA02").unwrap().map(|i| (i.id, i.updated_at)),
+                db.get_item_tag_history("a").unwrap().len(),
+                tag_moves(db, "E200AA01").len() + tag_moves(db, "E200AA02").len(),
+            )
+        };
+        let before = snapshot(&db);
+
+        // Relógio do totem atrás da última alteração de B vinda do servidor
+        let error = db.replace_rfid_tag("a", "E200AA02", None, 50).unwrap_err();
+        assert!(error.to_string().contains("pertence à peça 'b'"), "{}", error);
+        assert_eq!(snapshot(&db), before);
+        assert_eq!(db.lookup_rfid_item("E280A001").unwrap().unwrap().id, "a");
+
+        // Com a troca posterior à alteração de B, a tag muda de peça
+        assert!(db.replace_rfid_tag("a", "E200AA02", None, 150).unwrap());
+        assert_eq!(db.lookup_rfid_item("E200AA02").unwrap().unwrap().id, "a");
+        assert!(db.lookup_rfid_item("E200AA01").unwrap().is_none());
+        assert!(!db.replace_rfid_tag("zz", "E200AA03", None, 150).unwrap());
+    }
+
+    #[test]
+    fn settings_listing_hides_internal_keys() {
+        let db = TempDb::new("settings-list");
+        db.set_sync_config(&SyncConfig {
+            api_key: "segredo".into(),
+            ..SyncConfig::default()
+        })
+        .unwrap();
+        db.set_backoff_policy(&BackoffPolicy::default()).unwrap();
+        db.set_config("selectedClient", &serde_json::json!("c1")).unwrap();
+        db.set_config("myecolav:settings:v1", &serde_json::json!({"scale": {"port": "COM3"}}))
+            .unwrap();
+
+        let listed = db.list_config().unwrap();
+        let keys: Vec<&str> = listed.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["myecolav:settings:v1", "selectedClient"]);
+        assert!(listed.iter().all(|e| !e.value.to_string().contains("segredo")));
+        assert_eq!(db.get_sync_config().unwrap().api_key, "segredo");
+    }
+
+    #[test]
+    fn settings_import_runs_once_and_keeps_existing_values() {
+        let db = TempDb::new("settings-import");
+        db.set_config("selectedClient", &serde_json::json!("atual")).unwrap();
+
+        let legacy = vec![
+            ("selectedClient".to_string(), serde_json::json!("antigo")),
+            ("myecolav:settings:v1".to_string(), serde_json::json!({"totem": "t1"})),
+            ("sync:config".to_string(), serde_json::json!({"api_key": "injetada"})),
+            ("_meta:local_storage_imported".to_string(), serde_json::json!(false)),
+        ];
+        let imported = db.import_config(&legacy).unwrap();
+        let keys: Vec<&str> = imported.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["myecolav:settings:v1"]);
+        assert_eq!(db.get_config("selectedClient").unwrap().unwrap().value, "atual");
+        assert_eq!(db.get_sync_config().unwrap().api_key, SyncConfig::default().api_key);
+
+        // Segunda importação (outra webview, outro localStorage) não altera nada
+        let again = vec![("outra".to_string(), serde_json::json!(1))];
+        assert!(db.import_config(&again).unwrap().is_empty());
+        assert!(db.get_config("outra").unwrap().is_none());
+
+        let exported: Vec<(String, JsonValue)> = db
+            .list_config()
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.key, e.value))
+            .collect();
+        assert_eq!(
+            exported,
+            [
+                ("myecolav:settings:v1".to_string(), serde_json::json!({"totem": "t1"})),
+                ("selectedClient".to_string(), serde_json::json!("atual")),
+            ]
+        );
+
+        // O que a listagem exporta reimporta igual numa base nova
+        let fresh = TempDb::new("settings-reimport");
+        fresh.import_config(&exported).unwrap();
+        let reexported: Vec<(String, JsonValue)> = fresh
+            .list_config()
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.key, e.value))
+            .collect();
+        assert_eq!(reexported, exported);
+    }
+
+    fn linen(id: &str, name: &str, sku: Option<&str>, client_id: Option<&str>, updated_at: i64) -> LinenItem {
+        LinenItem {
+            id: id.into(),
+            name: name.into(),
+            sku: sku.map(Into::into),
+            client_id: client_id.map(Into::into),
+            updated_at,
+        }
+    }
+
+    fn sector(id: &str, name: &str, client_id: &str, updated_at: i64) -> Sector {
+        Sector {
+            id: id.into(),
+            name: name.into(),
+            description: None,
+            client_id: Some(client_id.into()),
+            updated_at,
+        }
+    }
+
+    fn bed(id: &str, number: &str, sector_id: &str, status: &str, token: &str, updated_at: i64) -> Bed {
+        Bed {
+            id: id.into(),
+            number: number.into(),
+            sector_id: Some(sector_id.into()),
+            status: Some(status.into()),
+            client_id: Some("c1".into()),
+            token: Some(token.into()),
+            updated_at,
+        }
+    }
+
+    #[test]
+    fn linen_items_round_trip_and_resync_replaces_rows() {
+        let db = TempDb::new("linen");
+        db.upsert_linen_item(&linen("l1", "Lençol", Some("LEN-01"), Some("c1"), 10)).unwrap();
+        let synced = db
+            .bulk_upsert_linen_items(&[
+                linen("l2", "avental", None, None, 10),
+                linen("l3", "Fronha", Some("FRO-01"), Some("c2"), 10),
+            ])
+            .unwrap();
+        assert_eq!(synced, 2);
+
+        let names = |db: &Database, client: &str| -> Vec<String> {
+            db.list_linen_items_by_client(client).unwrap().into_iter().map(|i| i.name).collect()
+        };
+        // Catálogo compartilhado (sem cliente) aparece para todos
+        assert_eq!(names(&db, "c1"), ["avental", "Lençol"]);
+        assert_eq!(names(&db, "c2"), ["avental", "Fronha"]);
+
+        let item = db.get_linen_item("l1").unwrap().unwrap();
+        assert_eq!((item.name.as_str(), item.sku.as_deref()), ("Lençol", Some("LEN-01")));
+        assert!(db.get_linen_item("zz").unwrap().is_none());
+
+        // Nova sincronização substitui a linha, inclusive a troca de cliente
+        db.bulk_upsert_linen_items(&[linen("l1", "Lençol solteiro", None, Some("c2"), 20)])
+            .unwrap();
+        let item = db.get_linen_item("l1").unwrap().unwrap();
+        assert_eq!(
+            (item.name.as_str(), item.sku, item.client_id.as_deref(), item.updated_at),
+            ("Lençol solteiro", None, Some("c2"), 20)
+        );
+        assert_eq!(names(&db, "c1"), ["avental"]);
+        assert_eq!(names(&db, "c2"), ["avental", "Fronha", "Lençol solteiro"]);
+
+        // Pull atrasado com a versão anterior não desfaz a atualização
+        db.bulk_upsert_linen_items(&[linen("l1", "Lençol", Some("LEN-01"), Some("c1"), 10)])
+            .unwrap();
+        db.upsert_linen_item(&linen("l1", "Lençol", Some("LEN-01"), Some("c1"), 15)).unwrap();
+        let item = db.get_linen_item("l1").unwrap().unwrap();
+        assert_eq!((item.name.as_str(), item.updated_at), ("Lençol solteiro", 20));
+    }
+
+    #[test]
+    fn sectors_and_beds_round_trip_and_resync_replaces_rows() {
+        let db = TempDb::new("sectors-beds");
+        db.bulk_upsert_sectors(&[
+            sector("s1", "UTI", "c1", 10),
+            sector("s2", "Enfermaria", "c1", 10),
+            sector("s3", "Outro hospital", "c2", 10),
+        ])
+        .unwrap();
+        db.bulk_upsert_beds(&[
+            bed("b1", "10", "s1", "free", "TK-1", 10),
+            bed("b2", "2", "s1", "occupied", "TK-2", 10),
+            bed("b3", "1", "s2", "free", "TK-3", 10),
+        ])
+        .unwrap();
+
+        let sector_names: Vec<String> =
+            db.list_sectors_by_client("c1").unwrap().into_iter().map(|s| s.name).collect();
+        assert_eq!(sector_names, ["Enfermaria", "UTI"]);
+
+        let bed_ids = |sector: Option<&str>, status: Option<&str>| -> Vec<String> {
+            db.list_beds("c1", sector, status).unwrap().into_iter().map(|b| b.id).collect()
+        };
+        assert_eq!(bed_ids(None, None), ["b2", "b1", "b3"]);
+        assert_eq!(bed_ids(Some("s1"), Some("free")), ["b1"]);
+        assert_eq!(bed_ids(None, Some("occupied")), ["b2"]);
+
+        let location = db.get_bed_by_token(" TK-3 ").unwrap().unwrap();
+        assert_eq!(location.bed.id, "b3");
+        assert_eq!(location.sector.unwrap().name, "Enfermaria");
+        assert!(db.get_bed_by_token("TK-9").unwrap().is_none());
+
+        // Nova sincronização: leito muda de setor, status e token
+        db.bulk_upsert_sectors(&[sector("s2", "Enfermaria 2", "c1", 20)]).unwrap();
+        db.bulk_upsert_beds(&[bed("b1", "10", "s2", "occupied", "TK-4", 20)]).unwrap();
+        assert!(db.get_bed_by_token("TK-1").unwrap().is_none());
+        let location = db.get_bed_by_token("TK-4").unwrap().unwrap();
+        assert_eq!(
+            (location.bed.status.as_deref(), location.bed.updated_at),
+            (Some("occupied"), 20)
+        );
+        assert_eq!(location.sector.unwrap().name, "Enfermaria 2");
+
+        // Pull atrasado: a versão antiga não volta o leito para livre nem o setor ao nome antigo
+        db.bulk_upsert_sectors(&[sector("s2", "Enfermaria", "c1", 10)]).unwrap();
+        db.bulk_upsert_beds(&[bed("b1", "10", "s1", "free", "TK-1", 15)]).unwrap();
+        assert!(db.get_bed_by_token("TK-1").unwrap().is_none());
+        let location = db.get_bed_by_token("TK-4").unwrap().unwrap();
+        assert_eq!(
+            (location.bed.sector_id.as_deref(), location.bed.status.as_deref()),
+            (Some("s2"), Some("occupied"))
+        );
+        assert_eq!(location.sector.unwrap().name, "Enfermaria 2");
+        assert_eq!(bed_ids(None, Some("free")), ["b3"]);
+
+        let tree: Vec<(String, Vec<String>)> = db
+            .get_sector_tree("c1", None)
+            .unwrap()
+            .into_iter()
+            .map(|node| (node.sector.id, node.beds.into_iter().map(|b| b.id).collect()))
+            .collect();
+        assert_eq!(
+            tree,
+            [
+                ("s2".to_string(), vec!["b3".to_string(), "b1".to_string()]),
+                ("s1".to_string(), vec!["b2".to_string()]),
+            ]
+        );
+    }
+
+    fn sync_batch(items: Vec<RfidItem>, deleted: &[(&str, i64)]) -> RfidSyncBatch {
+        RfidSyncBatch {
+            items,
+            deleted: deleted
+                .iter()
+                .map(|(id, deleted_at)| RfidTombstone {
+                    id: id.to_string(),
+                    deleted_at: *deleted_at,
+                })
+                .collect(),
+            cursor: None,
+        }
+    }
+
+    #[test]
+    fn tombstone_removes_piece_and_older_pulls_do_not_bring_it_back() {
+        let db = TempDb::new("tombstone");
+        db.upsert_rfid_item(&rfid_item("a", "E200AA01", None, 10)).unwrap();
+
+        let summary = db.apply_rfid_sync(&sync_batch(Vec::new(), &[("a", 20)])).unwrap();
+        assert_eq!((summary.upserted, summary.deleted), (0, 1));
+        assert!(db.lookup_rfid_item("E200AA01").unwrap().is_none());
+        let history = db.get_item_tag_history("a").unwrap();
+        assert_eq!(history[0].detached_at, Some(20));
+
+        // Versões anteriores à remoção chegam atrasadas (pull ou cache local)
+        db.apply_rfid_sync(&sync_batch(vec![rfid_item("a", "E200AA01", None, 15)], &[]))
+            .unwrap();
+        db.upsert_rfid_item(&rfid_item("a", "E200AA01", None, 20)).unwrap();
+        assert!(db.lookup_rfid_item("E200AA01").unwrap().is_none());
+
+        // Tombstone repetido e mais antigo não recua a data de remoção
+        db.apply_rfid_sync(&sync_batch(Vec::new(), &[("a", 12)])).unwrap();
+        db.upsert_rfid_item(&rfid_item("a", "E200AA01", None, 18)).unwrap();
+        assert!(db.lookup_rfid_item("E200AA01").unwrap().is_none());
+
+        // Peça reativada no servidor depois da remoção volta ao cache
+        db.apply_rfid_sync(&sync_batch(vec![rfid_item("a", "E200AA01", None, 25)], &[]))
+            .unwrap();
+        assert_eq!(db.lookup_rfid_item("E200AA01").unwrap().unwrap().updated_at, 25);
+    }
+
+    #[test]
+    fn stale_tombstone_keeps_live_piece_and_its_tag_history() {
+        let db = TempDb::new("stale-tombstone");
+        db.bulk_upsert_rfid_items(&[rfid_item("a", "E200AA01", None, 200)]).unwrap();
+        let before = history_rows(db.get_item_tag_history("a").unwrap());
+
+        let summary = db.apply_rfid_sync(&sync_batch(Vec::new(), &[("a", 50)])).unwrap();
+        assert_eq!(summary.deleted, 0);
+        assert_eq!(db.lookup_rfid_item("E200AA01").unwrap().unwrap().id, "a");
+        assert_eq!(history_rows(db.get_item_tag_history("a").unwrap()), before);
+
+        // A próxima versão não abre um período duplicado para a mesma tag
+        db.bulk_upsert_rfid_items(&[rfid_item("a", "E200AA01", None, 300)]).unwrap();
+        assert_eq!(history_rows(db.get_item_tag_history("a").unwrap()), before);
+    }
+
+    #[test]
+    fn sync_batch_applies_upserts_and_tombstones_atomically() {
+        let db = TempDb::new("tombstone-tx");
+        db.upsert_rfid_item(&rfid_item("a", "E200AA01", None, 10)).unwrap();
+
+        // Falha no meio do lote (depois do tombstone e do primeiro upsert)
+        db.conn
+            .lock()
+            .unwrap()
+            .execute_batch(
+                "CREATE TEMP TRIGGER boom BEFORE INSERT ON rfid_items
+                 WHEN NEW.id = 'x' BEGIN SELECT RAISE(ABORT, 'falha simulada'); END;",
+            )
+            .unwrap();
+        let batch = sync_batch(
+            vec![rfid_item("b", "E200AA02", None, 30), rfid_item("x", "E200AA03", None, 30)],
+            &[("a", 20)],
+        );
+        assert!(db.apply_rfid_sync(&batch).is_err());
+        assert_eq!(db.lookup_rfid_item("E200AA01").unwrap().unwrap().id, "a");
+        assert!(db.lookup_rfid_item("E200AA02").unwrap().is_none());
+        assert!(db.get_item_tag_history("a").unwrap()[0].detached_at.is_none());
+        assert_eq!(db.get_sync_cursor(Database::RFID_SYNC_ENTITY).unwrap().high_water_mark, None);
+
+        db.conn.lock().unwrap().execute_batch("DROP TRIGGER boom").unwrap();
+        let summary = db.apply_rfid_sync(&batch).unwrap();
+        assert_eq!((summary.upserted, summary.deleted), (2, 1));
+        assert!(db.lookup_rfid_item("E200AA01").unwrap().is_none());
+        assert_eq!(db.lookup_rfid_item("E200AA02").unwrap().unwrap().id, "b");
+        assert_eq!(db.get_sync_cursor(Database::RFID_SYNC_ENTITY).unwrap().high_water_mark, Some(30));
+    }
+
+    #[test]
+    fn malformed_rows_are_skipped_and_reported_without_stalling_sync() {
+        let db = TempDb::new("malformed");
+        let summary = db
+            .bulk_upsert_rfid_items(&[
+                rfid_item("a", "e2-00-aa-01", None, 10),
+                rfid_item("bad", "E200-XYZ", None, 10),
+                rfid_item("odd", "E200A", None, 10),
+                rfid_item("b", "E200AA02", Some("tid?"), 10),
+            ])
+            .unwrap();
+        assert_eq!(summary.upserted, 1);
+        let rejected: Vec<_> = summary.rejected.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(rejected, vec!["bad", "odd", "b"]);
+        assert_eq!(summary.rejected[0].tag, "E200-XYZ");
+        assert!(summary.rejected[0].error.contains("'X'"), "{}", summary.rejected[0].error);
+        assert!(db.lookup_rfid_item("E200AA01").unwrap().is_some());
+
+        // No pull, o restante do lote e o cursor avançam normalmente
+        let summary = db
+            .apply_rfid_sync(&RfidSyncBatch {
+                cursor: Some("c2".to_string()),
+                ..sync_batch(
+                    vec![rfid_item("c", "", None, 40), rfid_item("d", "E200AA04", None, 30)],
+                    &[("a", 20)],
+                )
+            })
+            .unwrap();
+        assert_eq!((summary.upserted, summary.deleted, summary.rejected.len()), (1, 1, 1));
+        assert!(db.lookup_rfid_item("E200AA04").unwrap().is_some());
+        let cursor = db.get_sync_cursor(Database::RFID_SYNC_ENTITY).unwrap();
+        assert_eq!((cursor.cursor.as_deref(), cursor.high_water_mark), (Some("c2"), Some(40)));
+
+        // Upsert avulso continua recusando a tag inválida
+        assert!(db.upsert_rfid_item(&rfid_item("e", "zz", None, 10)).is_err());
+    }
+
+    #[test]
+    fn retry_after_crash_reuses_idempotency_key() {
+        let mut db = TempDb::new("idempotency");
+        let distribute = |tag: &str| {
+            Operation::parse("distribute", &format!(r#"{{"tags":["{}"],"sectorId":"s1"}}"#, tag))
+                .unwrap()
+        };
+        let queued = db.queue_operation(&distribute("E200AA01")).unwrap();
+        let other = db.queue_operation(&distribute("E200AA02")).unwrap();
+        assert_ne!(queued.idempotency_key, other.idempotency_key);
+
+        // Primeira tentativa: o servidor aceita, mas o totem cai antes do complete_operation
+        let first = db.claim_operations_at(10, 60, LATER).unwrap();
+        assert_eq!(first[0].idempotency_key, queued.idempotency_key);
+
+        // O lease vence e a mesma operação volta com a mesma chave
+        db.reopen();
+        let retry = db.claim_operations_at(10, 60, LATER + 60).unwrap();
+        assert_eq!(retry[0].id, queued.id);
+        assert_eq!(retry[0].idempotency_key, queued.idempotency_key);
+        db.release_operations(retry[0].lease_token.as_deref().unwrap()).unwrap();
+
+        // A chave também sobrevive a falhas, à dead-letter e ao reenfileiramento
+        for _ in 0..MAX_OPERATION_RETRIES {
+            fail_attempt(&db, queued.id, "timeout");
+        }
+        let dead = db.get_failed_operation(queued.id).unwrap().unwrap();
+        assert_eq!(dead.operation.idempotency_key, queued.idempotency_key);
+        db.requeue_failed_operation(queued.id, None).unwrap();
+        let requeued = db.get_pending_operations(10).unwrap();
+        assert_eq!(requeued[0].idempotency_key, queued.idempotency_key);
+    }
+
+    #[test]
+    fn blocked_predecessor_holds_back_only_its_dependents() {
+        let db = TempDb::new("dependencies");
+        let op = |kind: &str, payload: &str| Operation::parse(kind, payload).unwrap();
+
+        let associate = db
+            .queue_operation(&op("associate", r#"{"tags":["E200AA01"],"itemId":"lencol"}"#))
+            .unwrap();
+        let distribute = db
+            .queue_operation(&op("distribute", r#"{"tags":["E200AA01"],"bedId":"b1"}"#))
+            .unwrap();
+        let unrelated = db
+            .queue_operation(&op("reception", r#"{"rfidTagUid":"E200BB02"}"#))
+            .unwrap();
+        assert_eq!(due_ids(&db), vec![associate.id, unrelated.id]);
+
+        // Associação falha e vai para backoff/dead-letter: a distribuição espera
+        for _ in 0..MAX_OPERATION_RETRIES {
+            fail_attempt(&db, associate.id, "409");
+        }
+        assert_eq!(due_ids(&db), vec![unrelated.id]);
+
+        db.requeue_failed_operation(associate.id, None).unwrap();
+        complete_attempt(&db, associate.id);
+        assert_eq!(due_ids(&db), vec![distribute.id, unrelated.id]);
+
+        // Baixa pendente: nova associação da mesma tag abre outro ciclo e espera
+        complete_attempt(&db, distribute.id);
+        let retire = db
+            .queue_operation(&op("retire", r#"{"tag":"E200AA01","reason":"rasgado"}"#))
+            .unwrap();
+        let reuse = db
+            .queue_operation(&op("associate", r#"{"tags":["E200AA01"],"itemId":"toalha"}"#))
+            .unwrap();
+        assert_eq!(due_ids(&db), vec![unrelated.id, retire.id]);
+        complete_attempt(&db, retire.id);
+        assert_eq!(due_ids(&db), vec![unrelated.id, reuse.id]);
+    }
+
+    // Instante em que qualquer backoff já venceu
+    const LATER: i64 = i64::MAX / 2;
+
+    // Ids liberados para envio agora, sem deixar nada reservado
+    fn due_ids(db: &Database) -> Vec<i64> {
+        let claimed = db.claim_operations_at(100, 60, LATER).unwrap();
+        if let Some(token) = claimed.first().and_then(|op| op.lease_token.as_deref()) {
+            db.release_operations(token).unwrap();
+        }
+        claimed.iter().map(|op| op.id).collect()
+    }
+
+    // Reserva as operações liberadas e devolve o lease de `id`
+    fn claim_for(db: &Database, id: i64) -> String {
+        let claimed = db.claim_operations_at(100, 60, LATER).unwrap();
+        claimed
+            .iter()
+            .find(|op| op.id == id)
+            .and_then(|op| op.lease_token.clone())
+            .unwrap_or_else(|| panic!("operação {} não liberada para envio", id))
+    }
+
+    // Tentativa de envio que falha; retorna true se foi para a dead-letter
+    fn fail_attempt(db: &Database, id: i64, error: &str) -> bool {
+        let token = claim_for(db, id);
+        let dead = db.fail_operation(id, &token, error).unwrap();
+        db.release_operations(&token).unwrap();
+        dead
+    }
+
+    fn complete_attempt(db: &Database, id: i64) {
+        let token = claim_for(db, id);
+        assert!(db.complete_operation(id, &token).unwrap());
+        db.release_operations(&token).unwrap();
+    }
+
+    // Envia (reserva e conclui) a única operação liberada e devolve o id dela
+    fn send_next(db: &Database) -> i64 {
+        let claimed = db.claim_operations_at(10, 60, LATER).unwrap();
+        assert_eq!(claimed.len(), 1, "esperava uma única operação liberada");
+        let token = claimed[0].lease_token.as_deref().unwrap();
+        assert!(db.complete_operation(claimed[0].id, token).unwrap());
+        claimed[0].id
+    }
+
+    #[test]
+    fn laundry_cycle_reaches_server_in_causal_order() {
+        let db = TempDb::new("cycle");
+        let op = |kind: &str, payload: &str| Operation::parse(kind, payload).unwrap();
+
+        // Ciclo completo da mesma peça: sai para o leito, volta suja, sai de novo
+        let first = db
+            .queue_operation(&op("distribute", r#"{"tags":["E200AA01"],"bedId":"b1"}"#))
+            .unwrap();
+        let reception = db
+            .queue_operation(&op("reception", r#"{"rfidTagUid":"E200AA01"}"#))
+            .unwrap();
+        let second = db
+            .queue_operation(&op("distribute", r#"{"tags":["E200AA01"],"bedId":"b2"}"#))
+            .unwrap();
+        let retire = db
+            .queue_operation(&op("retire", r#"{"tag":"E200AA01","reason":"rasgado"}"#))
+            .unwrap();
+        let associate = db
+            .queue_operation(&op("associate", r#"{"tags":["E200AA01"],"itemId":"toalha"}"#))
+            .unwrap();
+
+        let sent: Vec<i64> = (0..5).map(|_| send_next(&db)).collect();
+        assert_eq!(
+            sent,
+            vec![first.id, reception.id, second.id, retire.id, associate.id]
+        );
+    }
+
+    #[test]
+    fn expired_lease_returns_to_queue_and_stale_token_is_rejected() {
+        let db = TempDb::new("leases");
+        let retire = |tag: &str| {
+            Operation::parse("retire", &format!(r#"{{"tag":"{}","reason":"rasgado"}}"#, tag)).unwrap()
+        };
+        let first = db.queue_operation(&retire("E200AA01")).unwrap();
+        let second = db.queue_operation(&retire("E200AA02")).unwrap();
+        let now = chrono::Utc::now().timestamp();
+
+        // Cada loop reserva um lote próprio; enquanto o lease vale ninguém mais vê
+        let stale = db.claim_operations_at(1, 60, now).unwrap();
+        assert_eq!(stale.iter().map(|op| op.id).collect::<Vec<_>>(), vec![first.id]);
+        let other = db.claim_operations_at(10, 60, now).unwrap();
+        assert_eq!(other.iter().map(|op| op.id).collect::<Vec<_>>(), vec![second.id]);
+        assert!(db.claim_operations_at(10, 60, now + 59).unwrap().is_empty());
+
+        // Lease vencido: as duas voltam para a fila sem contar tentativa
+        let reclaimed = db.claim_operations_at(10, 60, now + 60).unwrap();
+        assert_eq!(reclaimed.len(), 2);
+        assert!(reclaimed.iter().all(|op| op.retry_count == 0));
+        let token = reclaimed[0].lease_token.as_deref().unwrap();
+
+        // O dono antigo não conclui nem registra falha com o token vencido
+        let stale_token = stale[0].lease_token.as_deref().unwrap();
+        let other_token = other[0].lease_token.as_deref().unwrap();
+        assert!(!db.complete_operation(first.id, stale_token).unwrap());
+        assert!(!db.fail_operation(second.id, other_token, "timeout").unwrap());
+        let pending = db.get_pending_operations(10).unwrap();
+        assert_eq!(pending.len(), 2);
+        assert!(pending.iter().all(|op| op.retry_count == 0 && op.last_error.is_none()));
+
+        assert!(db.complete_operation(first.id, token).unwrap());
+        db.fail_operation(second.id, token, "timeout").unwrap();
+        let pending = db.get_pending_operations(10).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!((pending[0].id, pending[0].retry_count), (second.id, 1));
+        assert!(pending[0].lease_token.is_none());
+    }
+
+    #[test]
+    fn due_operations_follow_backoff_leases_and_predecessors_without_leasing() {
+        let db = TempDb::new("due");
+        let queue = |kind: &str, payload: &str| {
+            db.queue_operation(&Operation::parse(kind, payload).unwrap()).unwrap().id
+        };
+        let associate = queue("associate", r#"{"tags":["E200AA01"],"itemId":"lencol"}"#);
+        let distribute = queue("distribute", r#"{"tags":["E200AA01"],"bedId":"b1"}"#);
+        let reception = queue("reception", r#"{"rfidTagUid":"E200BB02"}"#);
+        let now = chrono::Utc::now().timestamp();
+        let due = |at: i64| -> Vec<i64> {
+            db.get_due_operations(10, at).unwrap().iter().map(|op| op.id).collect()
+        };
+
+        // Distribuição espera a associação da mesma tag; a leitura não reserva
+        assert_eq!(due(now), vec![associate, reception]);
+        assert_eq!(due(now), vec![associate, reception]);
+        assert!(db.get_pending_operations(10).unwrap().iter().all(|op| op.lease_token.is_none()));
+
+        // Em backoff a associação sai da lista, mas continua segurando a distribuição
+        fail_attempt(&db, associate, "timeout");
+        assert_eq!(due(now), vec![reception]);
+        assert_eq!(due(LATER), vec![associate, reception]);
+
+        // Reservada por um loop, some até o lease vencer
+        let token = claim_for(&db, reception);
+        assert_eq!(due(now), Vec::<i64>::new());
+        db.release_operations(&token).unwrap();
+
+        complete_attempt(&db, associate);
+        assert_eq!(due(LATER), vec![distribute, reception]);
+    }
+
+    #[test]
+    fn leaseless_commands_respect_live_leases() {
+        let db = TempDb::new("leaseless");
+        let queued = db
+            .queue_operation(&Operation::parse("retire", r#"{"tag":"E200AA01","reason":"rasgado"}"#).unwrap())
+            .unwrap();
+
+        // Reservada pelo loop de envio: os comandos antigos não mexem nela
+        let token = claim_for(&db, queued.id);
+        assert!(!db.increment_retry_count(queued.id, "timeout").unwrap());
+        assert!(!db.delete_operation(queued.id).unwrap());
+        let pending = db.get_pending_operations(10).unwrap();
+        assert_eq!((pending[0].retry_count, pending[0].lease_token.as_deref()), (0, Some(token.as_str())));
+        db.release_operations(&token).unwrap();
+
+        assert!(!db.increment_retry_count(queued.id, "timeout").unwrap());
+        let pending = db.get_pending_operations(10).unwrap();
+        assert_eq!((pending[0].retry_count, pending[0].lease_token.as_ref()), (1, None));
+        assert_eq!(pending[0].last_error.as_deref(), Some("timeout"));
+
+        assert!(db.delete_operation(queued.id).unwrap());
+        assert!(db.get_pending_operations(10).unwrap().is_empty());
+        assert!(!db.delete_operation(queued.id).unwrap());
+    }
+
+    #[test]
+    fn requeued_cart_is_coalesced_until_sent() {
+        let db = TempDb::new("coalesce");
+        let reception = |tag: &str| {
+            Operation::parse("reception", &format!(r#"{{"rfidTagUid":"{}","sectorId":"s1"}}"#, tag))
+                .unwrap()
+        };
+
+        let first = db.queue_operation(&reception("E200AA01")).unwrap();
+        assert_eq!(first.outcome, QueueOutcome::Queued);
+        let again = db.queue_operation(&reception("e2-00-aa-01")).unwrap();
+        assert_eq!(again.outcome, QueueOutcome::Dropped);
+        assert_eq!((again.id, &again.idempotency_key), (first.id, &first.idempotency_key));
+
+        let distribute = |tags: &str| {
+            Operation::parse("distribute", &format!(r#"{{"tags":{},"bedId":"b1"}}"#, tags)).unwrap()
+        };
+        let cart = db.queue_operation(&distribute(r#"["E200BB01"]"#)).unwrap();
+        let merged = db.queue_operation(&distribute(r#"["E200BB01","E200BB02"]"#)).unwrap();
+        assert_eq!((merged.id, merged.outcome), (cart.id, QueueOutcome::Merged));
+        let pending = db.get_pending_operations(10).unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[1].payload, r#"{"bedId":"b1","tags":["E200BB01","E200BB02"]}"#);
+
+        // Depois da primeira tentativa a operação pode ter chegado ao servidor
+        fail_attempt(&db, first.id, "timeout");
+        let after_send = db.queue_operation(&reception("E200AA01")).unwrap();
+        assert_eq!(after_send.outcome, QueueOutcome::Queued);
+        assert_ne!(after_send.id, first.id);
+    }
+
+    #[test]
+    fn coalescing_never_skips_over_a_later_operation_on_the_same_tag() {
+        let db = TempDb::new("coalesce-order");
+        let op = |kind: &str, payload: &str| Operation::parse(kind, payload).unwrap();
+        let reception = || op("reception", r#"{"rfidTagUid":"E200AA01"}"#);
+
+        // Volta suja, sai para o leito e volta de novo: são duas recepções reais
+        let first = db.queue_operation(&reception()).unwrap();
+        let distribute = db
+            .queue_operation(&op("distribute", r#"{"tags":["E200AA01"],"bedId":"b1"}"#))
+            .unwrap();
+        let second = db.queue_operation(&reception()).unwrap();
+        assert_eq!(second.outcome, QueueOutcome::Queued);
+        assert_ne!(second.id, first.id);
+
+        // A releitura logo em seguida ainda agrupa com a última recepção
+        let reread = db.queue_operation(&reception()).unwrap();
+        assert_eq!((reread.id, reread.outcome), (second.id, QueueOutcome::Dropped));
+
+        // Reservada e devolvida por erro de rede: pode ter chegado ao servidor
+        let token = claim_for(&db, first.id);
+        db.release_operations(&token).unwrap();
+        complete_attempt(&db, first.id);
+        complete_attempt(&db, distribute.id);
+        let token = claim_for(&db, second.id);
+        db.release_operations(&token).unwrap();
+        let after_timeout = db.queue_operation(&reception()).unwrap();
+        assert_eq!(after_timeout.outcome, QueueOutcome::Queued);
+
+        let sent: Vec<i64> = (0..2).map(|_| send_next(&db)).collect();
+        assert_eq!(sent, vec![second.id, after_timeout.id]);
+    }
+
+    #[test]
+    fn dead_letter_requeue_revalidates_and_discard_removes_everything() {
+        let db = TempDb::new("dead-letter");
+        let detach = Operation::parse("detach_tag", r#"{"tag":"e2-00"}"#).unwrap();
+        let queued = db.queue_operation(&detach).unwrap();
+
+        for attempt in 1..MAX_OPERATION_RETRIES {
+            assert!(!fail_attempt(&db, queued.id, &format!("HTTP 500 #{}", attempt)));
+        }
+        assert!(fail_attempt(&db, queued.id, "HTTP 422"));
+        assert_eq!((db.get_pending_count().unwrap(), db.get_failed_count().unwrap()), (0, 1));
+        let errors = |db: &Database| -> Vec<String> {
+            let detail = db.get_failed_operation(queued.id).unwrap().unwrap();
+            detail.errors.into_iter().map(|e| e.error).collect()
+        };
+        assert_eq!(errors(&db).len(), MAX_OPERATION_RETRIES as usize);
+        assert_eq!(errors(&db).last().unwrap(), "HTTP 422");
+
+        // Payload corrigido passa pela validação do enqueue; inválido não sai da dead-letter
+        assert!(db.requeue_failed_operation(queued.id, Some(r#"{"tag":"xyz"}"#)).is_err());
+        assert!(db.requeue_failed_operation(queued.id, Some(r#"{"tags":["E201"]}"#)).is_err());
+        assert_eq!((db.get_pending_count().unwrap(), db.get_failed_count().unwrap()), (0, 1));
+
+        assert!(db.requeue_failed_operation(queued.id, Some(r#"{"tag":"e2 01"}"#)).unwrap());
+        let pending = db.get_pending_operations(10).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, queued.id);
+        assert_eq!(pending[0].payload, r#"{"tag":"E201"}"#);
+        assert_eq!(pending[0].payload_version, PAYLOAD_SCHEMA_VERSION);
+        assert_eq!(pending[0].retry_count, 0);
+        assert_eq!(pending[0].idempotency_key, queued.idempotency_key);
+        assert_eq!(db.get_failed_count().unwrap(), 0);
+
+        // O histórico de erros acompanha a operação na volta para a dead-letter
+        for _ in 0..MAX_OPERATION_RETRIES {
+            fail_attempt(&db, queued.id, "HTTP 409");
+        }
+        let history = errors(&db);
+        assert_eq!(history.len(), 2 * MAX_OPERATION_RETRIES as usize);
+        assert_eq!(history[0], "HTTP 500 #1");
+        assert_eq!(history[MAX_OPERATION_RETRIES as usize - 1], "HTTP 422");
+        assert_eq!(history.last().unwrap(), "HTTP 409");
+
+        assert!(db.discard_failed_operation(queued.id).unwrap());
+        assert!(db.get_failed_operation(queued.id).unwrap().is_none());
+        assert!(db.list_failed_operations(10).unwrap().is_empty());
+        assert_eq!((db.get_pending_count().unwrap(), db.get_failed_count().unwrap()), (0, 0));
+        {
+            let conn = db.conn.lock().unwrap();
+            let leftovers: i64 = conn
+                .query_row(
+                    "SELECT (SELECT COUNT(*) FROM operation_errors)
+                          + (SELECT COUNT(*) FROM operation_dependencies)",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(leftovers, 0);
+        }
+        assert!(!db.discard_failed_operation(queued.id).unwrap());
+        assert!(!db.requeue_failed_operation(queued.id, None).unwrap());
+        assert!(!db.requeue_failed_operation(queued.id, Some(r#"{"tag":"E201"}"#)).unwrap());
+    }
+
+    #[test]
+    fn pending_operations_project_onto_cached_items_until_settled() {
+        let db = TempDb::new("overlay");
+        db.upsert_rfid_item(&rfid_item("p1", "E200AA01", None, 100)).unwrap();
+        let status = |db: &Database| {
+            let item = db.lookup_rfid_item("E200AA01").unwrap().unwrap();
+            (item.status.unwrap(), item.pending_sync)
+        };
+
+        let distribute =
+            Operation::parse("distribute", r#"{"tags":["E200AA01"],"bedId":"b1"}"#).unwrap();
+        let queued = db.queue_operation(&distribute).unwrap();
+        assert_eq!(status(&db), ("DISTRIBUIDO".to_string(), true));
+        let bulk = db.lookup_rfid_items_bulk(&["e2-00-aa-01".to_string()]).unwrap();
+        assert!(bulk.found["e2-00-aa-01"].pending_sync);
+
+        // Falha definitiva desfaz a projeção; reenfileirar projeta de novo
+        for _ in 0..MAX_OPERATION_RETRIES {
+            fail_attempt(&db, queued.id, "422");
+        }
+        assert_eq!(status(&db), ("EM_USO".to_string(), false));
+        db.requeue_failed_operation(queued.id, None).unwrap();
+        assert_eq!(status(&db), ("DISTRIBUIDO".to_string(), true));
+
+        // Servidor aceitou: o status vai para o cache sem adiantar o updated_at
+        let claimed = db.claim_operations(10, 60).unwrap();
+        db.complete_operation(queued.id, claimed[0].lease_token.as_deref().unwrap()).unwrap();
+        assert_eq!(status(&db), ("DISTRIBUIDO".to_string(), false));
+        assert_eq!(db.lookup_rfid_item("E200AA01").unwrap().unwrap().updated_at, 100);
+    }
+
+    #[test]
+    fn refuses_database_from_newer_binary() {
+        let mut db = TempDb::new("newer");
+        db.close();
+
+        {
+            let conn = Connection::open(db.path()).unwrap();
+            conn.execute(
+                "INSERT INTO schema_version (version, applied_at) VALUES (?1, 0)",
+                params![SCHEMA_VERSION + 1],
+            )
+            .unwrap();
+        }
+
+        assert!(Database::new(db.path().to_path_buf()).is_err());
+    }
+}
@@ -1,74 +1,55 @@
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
 use tauri::{Manager, State, WindowEvent};
 
 mod backend;
+mod backoff;
 mod commands;
 mod db;
+mod operation;
+mod scale;
+mod scale_protocol;
+mod scale_stability;
+mod sync;
+mod tag;
 
 use backend::BackendController;
 use db::Database;
+use scale::{ScaleController, ScaleSnapshot, ScaleStatus, SerialConfig};
+use scale_stability::StabilityConfig;
+use sync::SyncController;
 
-// Estado global para armazenar o último peso lido
-struct ScaleState {
-    last_weight: Arc<Mutex<f64>>,
-    connected: Arc<Mutex<bool>>,
-}
-
+// Consulta pontual; a UI acompanha a balança pelo evento scale-weight
 #[tauri::command]
-fn read_scale_weight(state: State<ScaleState>) -> Result<(f64, bool), String> {
-    let weight = *state.last_weight.lock().unwrap();
-    let connected = *state.connected.lock().unwrap();
-    Ok((weight, connected))
+fn read_scale_weight(controller: State<ScaleController>) -> Result<ScaleSnapshot, String> {
+    Ok(controller.snapshot())
 }
 
+// (Re)inicia a leitura: um leitor já ativo é encerrado antes de abrir a porta
 #[tauri::command]
 fn start_scale_reader(
-    port: String,
-    _baud_rate: u32,
-    state: State<ScaleState>,
-) -> Result<String, String> {
-    use std::io::BufRead;
-    use std::thread;
+    config: SerialConfig,
+    stability: Option<StabilityConfig>,
+    controller: State<ScaleController>,
+    app_handle: tauri::AppHandle,
+) -> Result<ScaleStatus, String> {
+    controller.start(&app_handle, config, stability.unwrap_or_default())
+}
 
-    let last_weight = Arc::clone(&state.last_weight);
-    let connected = Arc::clone(&state.connected);
+#[tauri::command]
+fn stop_scale_reader(controller: State<ScaleController>) {
+    controller.stop();
+}
 
-    // Criar thread para ler continuamente da porta serial
-    thread::spawn(move || {
-        loop {
-            match std::fs::File::open(&port) {
-                Ok(file) => {
-                    *connected.lock().unwrap() = true;
-                    let reader = std::io::BufReader::new(file);
-                    for line in reader.lines() {
-                        if let Ok(data) = line {
-                            let data = data.trim();
-                            // Formato H/L: H0000.15 ou L0000.10
-                            if data.len() > 1
-                                && (data.starts_with('H')
-                                    || data.starts_with('L')
-                                    || data.starts_with('h')
-                                    || data.starts_with('l'))
-                            {
-                                let weight_str = &data[1..]; // Pular primeiro caractere (H ou L)
-                                if let Ok(weight) = weight_str.parse::<f64>() {
-                                    *last_weight.lock().unwrap() = weight;
-                                }
-                            }
-                        }
-                    }
-                }
-                Err(_) => {
-                    *connected.lock().unwrap() = false;
-                    thread::sleep(Duration::from_secs(2));
-                }
-            }
-            thread::sleep(Duration::from_millis(100));
-        }
-    });
+#[tauri::command]
+fn restart_scale_reader(
+    controller: State<ScaleController>,
+    app_handle: tauri::AppHandle,
+) -> Result<ScaleStatus, String> {
+    controller.restart(&app_handle)
+}
 
-    Ok("Scale reader started".to_string())
+#[tauri::command]
+fn get_scale_status(controller: State<ScaleController>) -> ScaleStatus {
+    controller.status()
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -88,41 +69,89 @@ pub fn run() {
     println!("✅ Banco de dados SQLite inicializado");
 
     tauri::Builder::default()
-        .manage(ScaleState {
-            last_weight: Arc::new(Mutex::new(0.0)),
-            connected: Arc::new(Mutex::new(false)),
-        })
+        .manage(ScaleController::default())
         .manage(database)
         .manage(BackendController::default())
-        .setup(|_app| {
+        .manage(SyncController::default())
+        .setup(|app| {
+            let handle = app.handle();
             #[cfg(not(debug_assertions))]
             {
-                let handle = _app.handle();
-                let controller = _app.state::<BackendController>();
+                let controller = app.state::<BackendController>();
                 controller.start(&handle);
             }
+
+            // Retoma a sincronização com a última configuração gravada, sem
+            // esperar a UI. Sem URL gravada, o start_sync da UI inicia depois.
+            let db = app.state::<Database>();
+            if let Ok(config) = db.get_sync_config() {
+                let _ = app
+                    .state::<SyncController>()
+                    .start(&handle, db.inner().clone(), config);
+            }
             Ok(())
         })
         .on_window_event(|event| {
             if let WindowEvent::CloseRequested { .. } = event.event() {
                 let controller = event.window().state::<BackendController>();
                 controller.shutdown();
+                event.window().state::<SyncController>().stop();
+                event.window().state::<ScaleController>().stop();
             }
         })
         .invoke_handler(tauri::generate_handler![
             read_scale_weight,
             start_scale_reader,
+            stop_scale_reader,
+            restart_scale_reader,
+            get_scale_status,
             commands::lookup_rfid_local,
+            commands::lookup_rfid_items_bulk,
             commands::cache_rfid_item,
             commands::bulk_cache_rfid_items,
+            commands::apply_rfid_sync,
+            commands::detach_rfid_tag_local,
+            commands::replace_rfid_tag_local,
+            commands::get_tag_history,
+            commands::search_rfid_local,
+            commands::cache_linen_item,
+            commands::bulk_cache_linen_items,
+            commands::list_linen_items_local,
+            commands::get_linen_item_local,
+            commands::bulk_cache_sectors,
+            commands::bulk_cache_beds,
+            commands::list_sectors_local,
+            commands::list_beds_local,
+            commands::get_bed_by_token_local,
+            commands::get_sector_tree_local,
             commands::queue_operation,
             commands::get_pending_operations,
+            commands::get_due_operations,
+            commands::get_backoff_policy,
+            commands::set_backoff_policy,
+            commands::claim_operations,
+            commands::complete_operation,
+            commands::fail_operation,
+            commands::release_operations,
             commands::delete_operation,
             commands::increment_retry_count,
             commands::get_pending_count,
+            commands::list_failed_operations,
+            commands::get_failed_operation,
+            commands::requeue_failed_operation,
+            commands::discard_failed_operation,
+            commands::start_sync,
+            commands::stop_sync,
+            commands::force_sync,
+            commands::get_sync_status,
             commands::get_last_sync,
             commands::update_sync_log,
             commands::get_db_stats,
+            commands::get_setting,
+            commands::set_setting,
+            commands::delete_setting,
+            commands::list_settings,
+            commands::import_settings,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
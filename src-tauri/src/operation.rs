@@ -0,0 +1,612 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::fmt;
+
+use crate::tag;
+
+// Versão do formato dos payloads gravados em pending_operations.
+// Incrementar quando algum struct abaixo mudar de forma incompatível.
+pub const PAYLOAD_SCHEMA_VERSION: i32 = 1;
+
+pub const TERMINAL_STAGE: u8 = 3;
+
+// Operações aceitas pela fila offline. Cada variante carrega o payload no
+// formato esperado pelo endpoint correspondente do servidor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "operation_type", content = "payload", rename_all = "snake_case")]
+pub enum Operation {
+    Distribute(DistributePayload),
+    Reception(ReceptionPayload),
+    Retire(RetirePayload),
+    Associate(AssociatePayload),
+    ReplaceTag(ReplaceTagPayload),
+    DetachTag(DetachTagPayload),
+    Nonconformity(NonconformityPayload),
+    Weighing(WeighingPayload),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DistributePayload {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rfid_item_ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sector_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bed_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ReceptionPayload {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rfid_tag_uid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rfid_item_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sector_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reader_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RetirePayload {
+    pub tag: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    pub reason: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub release_tag: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AssociatePayload {
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    pub item_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_number: Option<JsonValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ReplaceTagPayload {
+    pub old_tag: String,
+    pub new_tag: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rfid_item_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DetachTagPayload {
+    pub tag: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct NonconformityPayload {
+    pub tag: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    pub reason: String,
+}
+
+// Mesmo formato (snake_case, em português) do endpoint de pesagens
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WeighingPayload {
+    pub control_id: String,
+    pub peso_total: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cage_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peso_tara: Option<f64>,
+}
+
+// Resultado de comparar uma operação nova com outra ainda não enviada
+#[derive(Debug, Clone, PartialEq)]
+pub enum Coalesce {
+    // A nova não acrescenta nada: descartada
+    Drop,
+    // A existente passa a cobrir as duas
+    Merge(Operation),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvalidOperation {
+    UnknownType(String),
+    Malformed { operation_type: String, reason: String },
+    Invalid { operation_type: String, reason: String },
+}
+
+impl fmt::Display for InvalidOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidOperation::UnknownType(kind) => {
+                write!(f, "Tipo de operação desconhecido: {}", kind)
+            }
+            InvalidOperation::Malformed { operation_type, reason } => {
+                write!(f, "Payload inválido para '{}': {}", operation_type, reason)
+            }
+            InvalidOperation::Invalid { operation_type, reason } => {
+                write!(f, "Operação '{}' inválida: {}", operation_type, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidOperation {}
+
+impl Operation {
+    pub const TYPES: &'static [&'static str] = &[
+        "distribute",
+        "reception",
+        "retire",
+        "associate",
+        "replace_tag",
+        "detach_tag",
+        "nonconformity",
+        "weighing",
+    ];
+
+    // Converte o par (tipo, payload JSON) recebido da UI numa operação tipada,
+    // com tags normalizadas e regras de negócio básicas verificadas.
+    pub fn parse(operation_type: &str, payload: &str) -> Result<Operation, InvalidOperation> {
+        if !Self::TYPES.contains(&operation_type) {
+            return Err(InvalidOperation::UnknownType(operation_type.to_string()));
+        }
+
+        let malformed = |reason: String| InvalidOperation::Malformed {
+            operation_type: operation_type.to_string(),
+            reason,
+        };
+
+        let mut payload: JsonValue =
+            serde_json::from_str(payload).map_err(|e| malformed(e.to_string()))?;
+        tag::normalize_payload_tags(&mut payload).map_err(|e| malformed(e.to_string()))?;
+
+        let operation: Operation = serde_json::from_value(serde_json::json!({
+            "operation_type": operation_type,
+            "payload": payload,
+        }))
+        .map_err(|e| malformed(e.to_string()))?;
+
+        operation.validate()?;
+        Ok(operation)
+    }
+
+    pub fn operation_type(&self) -> &'static str {
+        match self {
+            Operation::Distribute(_) => "distribute",
+            Operation::Reception(_) => "reception",
+            Operation::Retire(_) => "retire",
+            Operation::Associate(_) => "associate",
+            Operation::ReplaceTag(_) => "replace_tag",
+            Operation::DetachTag(_) => "detach_tag",
+            Operation::Nonconformity(_) => "nonconformity",
+            Operation::Weighing(_) => "weighing",
+        }
+    }
+
+    pub fn payload_json(&self) -> String {
+        let value = match self {
+            Operation::Distribute(p) => serde_json::to_value(p),
+            Operation::Reception(p) => serde_json::to_value(p),
+            Operation::Retire(p) => serde_json::to_value(p),
+            Operation::Associate(p) => serde_json::to_value(p),
+            Operation::ReplaceTag(p) => serde_json::to_value(p),
+            Operation::DetachTag(p) => serde_json::to_value(p),
+            Operation::Nonconformity(p) => serde_json::to_value(p),
+            Operation::Weighing(p) => serde_json::to_value(p),
+        };
+        value.map(|v| v.to_string()).unwrap_or_default()
+    }
+
+    // Etapa no ciclo de vida da peça. Para a mesma tag/peça a ordem causal é
+    // associar → distribuir → receber → baixar; etapas terminais encerram o
+    // ciclo (a tag fica livre para uma nova associação).
+    pub fn stage(&self) -> Option<u8> {
+        match self {
+            Operation::Associate(_) => Some(0),
+            Operation::Distribute(_) | Operation::ReplaceTag(_) => Some(1),
+            Operation::Reception(_) | Operation::Nonconformity(_) => Some(2),
+            Operation::Retire(_) | Operation::DetachTag(_) => Some(TERMINAL_STAGE),
+            Operation::Weighing(_) => None,
+        }
+    }
+
+    // Status que a peça terá quando o servidor aceitar a operação; usado para
+    // projetar a fila sobre o cache local enquanto ela não sincroniza.
+    pub fn projected_status(&self) -> Option<&'static str> {
+        match self {
+            Operation::Distribute(_) => Some("DISTRIBUIDO"),
+            Operation::Reception(_) => Some("EXPURGO"),
+            Operation::Retire(_) => Some("BAIXADO"),
+            _ => None,
+        }
+    }
+
+    // Chaves de dependência ("tag:<TAG>" / "item:<id>") tocadas pela operação.
+    pub fn dependency_keys(&self) -> Vec<String> {
+        let tag = |t: &String| format!("tag:{}", t);
+        let item = |i: &String| format!("item:{}", i);
+
+        let mut keys: Vec<String> = match self {
+            Operation::Distribute(p) => {
+                p.tags.iter().map(tag).chain(p.rfid_item_ids.iter().map(item)).collect()
+            }
+            Operation::Reception(p) => {
+                p.rfid_tag_uid.iter().map(tag).chain(p.rfid_item_id.iter().map(item)).collect()
+            }
+            Operation::Retire(p) => vec![tag(&p.tag)],
+            Operation::Associate(p) => p.tags.iter().map(tag).collect(),
+            Operation::ReplaceTag(p) => [&p.old_tag, &p.new_tag]
+                .into_iter()
+                .map(tag)
+                .chain(p.rfid_item_id.iter().map(item))
+                .collect(),
+            Operation::DetachTag(p) => vec![tag(&p.tag)],
+            Operation::Nonconformity(p) => vec![tag(&p.tag)],
+            Operation::Weighing(_) => Vec::new(),
+        };
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    // Regras por tipo para operações equivalentes (mesmo destino e mesmas
+    // tags) enfileiradas de novo, p.ex. quando o operador relê o mesmo carrinho.
+    // Listas de tags com interseção são unidas; pesagens nunca são agrupadas,
+    // pois cada uma é um evento físico distinto.
+    pub fn coalesce(&self, newer: &Operation) -> Option<Coalesce> {
+        match (self, newer) {
+            (Operation::Distribute(old), Operation::Distribute(new))
+                if (&old.client_id, &old.sector_id, &old.bed_id)
+                    == (&new.client_id, &new.sector_id, &new.bed_id) =>
+            {
+                if !overlaps(&old.tags, &new.tags) && !overlaps(&old.rfid_item_ids, &new.rfid_item_ids)
+                {
+                    return None;
+                }
+                let merged = DistributePayload {
+                    tags: merge_lists(&old.tags, &new.tags).unwrap_or_else(|| old.tags.clone()),
+                    rfid_item_ids: merge_lists(&old.rfid_item_ids, &new.rfid_item_ids)
+                        .unwrap_or_else(|| old.rfid_item_ids.clone()),
+                    ..old.clone()
+                };
+                Some(Self::merge_or_drop(self, Operation::Distribute(merged)))
+            }
+            (Operation::Associate(old), Operation::Associate(new))
+                if (&old.item_id, &old.client_id, &old.batch_number)
+                    == (&new.item_id, &new.client_id, &new.batch_number)
+                    && overlaps(&old.tags, &new.tags) =>
+            {
+                let merged = AssociatePayload {
+                    tags: merge_lists(&old.tags, &new.tags).unwrap_or_else(|| old.tags.clone()),
+                    ..old.clone()
+                };
+                Some(Self::merge_or_drop(self, Operation::Associate(merged)))
+            }
+            (Operation::Reception(old), Operation::Reception(new))
+                if (&old.rfid_tag_uid, &old.rfid_item_id) == (&new.rfid_tag_uid, &new.rfid_item_id)
+                    && (&old.client_id, &old.sector_id, &old.reader_id)
+                        == (&new.client_id, &new.sector_id, &new.reader_id) =>
+            {
+                Some(Coalesce::Drop)
+            }
+            (Operation::Retire(old), Operation::Retire(new)) if old.tag == new.tag => {
+                Some(Coalesce::Drop)
+            }
+            (Operation::DetachTag(old), Operation::DetachTag(new)) if old.tag == new.tag => {
+                Some(Coalesce::Drop)
+            }
+            (Operation::ReplaceTag(old), Operation::ReplaceTag(new))
+                if (&old.old_tag, &old.new_tag) == (&new.old_tag, &new.new_tag) =>
+            {
+                Some(Coalesce::Drop)
+            }
+            (Operation::Nonconformity(old), Operation::Nonconformity(new))
+                if old.tag == new.tag && old.reason == new.reason =>
+            {
+                Some(Coalesce::Drop)
+            }
+            _ => None,
+        }
+    }
+
+    fn merge_or_drop(existing: &Operation, merged: Operation) -> Coalesce {
+        if *existing == merged {
+            Coalesce::Drop
+        } else {
+            Coalesce::Merge(merged)
+        }
+    }
+
+    fn validate(&self) -> Result<(), InvalidOperation> {
+        let invalid = |reason: &str| {
+            Err(InvalidOperation::Invalid {
+                operation_type: self.operation_type().to_string(),
+                reason: reason.to_string(),
+            })
+        };
+        let blank = |value: &str| value.trim().is_empty();
+
+        match self {
+            Operation::Distribute(p) if p.rfid_item_ids.is_empty() && p.tags.is_empty() => {
+                invalid("informe rfidItemIds ou tags")
+            }
+            Operation::Distribute(p) if p.bed_id.is_none() && p.sector_id.is_none() => {
+                invalid("informe o setor ou o leito de destino")
+            }
+            Operation::Reception(p) if p.rfid_tag_uid.is_none() && p.rfid_item_id.is_none() => {
+                invalid("informe rfidTagUid ou rfidItemId")
+            }
+            Operation::Retire(p) if blank(&p.reason) => invalid("motivo obrigatório"),
+            Operation::Associate(p) if p.tags.is_empty() => invalid("nenhuma tag para associar"),
+            Operation::Associate(p) if blank(&p.item_id) => invalid("itemId obrigatório"),
+            Operation::ReplaceTag(p) if p.old_tag == p.new_tag => {
+                invalid("a nova tag é igual à atual")
+            }
+            Operation::Nonconformity(p) if blank(&p.reason) => invalid("motivo obrigatório"),
+            Operation::Weighing(p) if blank(&p.control_id) => invalid("control_id obrigatório"),
+            Operation::Weighing(p) if !p.peso_total.is_finite() || p.peso_total <= 0.0 => {
+                invalid("peso_total deve ser maior que zero")
+            }
+            Operation::Weighing(p)
+                if p.peso_tara.is_some_and(|tara| !tara.is_finite() || tara < 0.0) =>
+            {
+                invalid("peso_tara não pode ser negativo")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn overlaps(a: &[String], b: &[String]) -> bool {
+    a.iter().any(|value| b.contains(value))
+}
+
+// União preservando a ordem original; None quando nada foi acrescentado.
+fn merge_lists(existing: &[String], newer: &[String]) -> Option<Vec<String>> {
+    let added: Vec<&String> = newer.iter().filter(|value| !existing.contains(value)).collect();
+    if added.is_empty() {
+        return None;
+    }
+    let mut merged = existing.to_vec();
+    merged.extend(added.into_iter().cloned());
+    Some(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(kind: &str, payload: &str) -> Operation {
+        Operation::parse(kind, payload).unwrap()
+    }
+
+    fn rejected(kind: &str, payload: &str) -> InvalidOperation {
+        Operation::parse(kind, payload).expect_err(payload)
+    }
+
+    fn invalid_reason(kind: &str, payload: &str) -> String {
+        match rejected(kind, payload) {
+            InvalidOperation::Invalid { reason, .. } => reason,
+            other => panic!("esperava regra de negócio para {}, veio {:?}", payload, other),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_operation_type() {
+        assert_eq!(
+            rejected("teleport", r#"{"tag":"AA01"}"#),
+            InvalidOperation::UnknownType("teleport".to_string())
+        );
+        assert!(matches!(rejected("Distribute", "{}"), InvalidOperation::UnknownType(_)));
+    }
+
+    #[test]
+    fn rejects_malformed_payloads() {
+        for (kind, payload) in [
+            ("distribute", r#"{"tags":["AA01"],"bedId":"#),
+            ("distribute", r#""AA01""#),
+            ("distribute", r#"{"tags":"AA01","bedId":"b1"}"#),
+            ("retire", r#"{"tag":"AA01"}"#),
+            ("weighing", r#"{"control_id":"c1","peso_total":"12,5"}"#),
+        ] {
+            assert!(
+                matches!(rejected(kind, payload), InvalidOperation::Malformed { .. }),
+                "{} {}",
+                kind,
+                payload
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        for (kind, payload) in [
+            ("distribute", r#"{"tags":["AA01"],"bedID":"b1"}"#),
+            ("reception", r#"{"rfidTagUid":"AA01","sector":"s1"}"#),
+            ("retire", r#"{"tag":"AA01","reason":"rasgado","typo":1}"#),
+            ("weighing", r#"{"control_id":"c1","pesoTotal":12.5}"#),
+        ] {
+            match rejected(kind, payload) {
+                InvalidOperation::Malformed { reason, .. } => {
+                    assert!(reason.contains("unknown field"), "{}", reason)
+                }
+                other => panic!("esperava campo desconhecido em {}, veio {:?}", payload, other),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_tags() {
+        for (kind, payload) in [
+            ("distribute", r#"{"tags":["AA0Z"],"bedId":"b1"}"#),
+            ("associate", r#"{"tags":["AA01",""],"itemId":"lencol"}"#),
+            ("retire", r#"{"tag":"A","reason":"rasgado"}"#),
+            ("replace_tag", r#"{"oldTag":"AA01","newTag":"tag nova"}"#),
+        ] {
+            assert!(
+                matches!(rejected(kind, payload), InvalidOperation::Malformed { .. }),
+                "{} {}",
+                kind,
+                payload
+            );
+        }
+    }
+
+    #[test]
+    fn enforces_business_rules() {
+        for (kind, payload, reason) in [
+            ("associate", r#"{"tags":[],"itemId":"lencol"}"#, "nenhuma tag para associar"),
+            ("associate", r#"{"tags":["AA01"],"itemId":" "}"#, "itemId obrigatório"),
+            ("distribute", r#"{"tags":[],"bedId":"b1"}"#, "informe rfidItemIds ou tags"),
+            ("distribute", r#"{"tags":["AA01"]}"#, "informe o setor ou o leito de destino"),
+            ("reception", r#"{"sectorId":"s1"}"#, "informe rfidTagUid ou rfidItemId"),
+            ("retire", r#"{"tag":"AA01","reason":""}"#, "motivo obrigatório"),
+            ("nonconformity", r#"{"tag":"AA01","reason":"  "}"#, "motivo obrigatório"),
+            ("replace_tag", r#"{"oldTag":"aa01","newTag":"AA-01"}"#, "a nova tag é igual à atual"),
+            ("weighing", r#"{"control_id":"","peso_total":3}"#, "control_id obrigatório"),
+            ("weighing", r#"{"control_id":"c1","peso_total":0}"#, "peso_total deve ser maior que zero"),
+            ("weighing", r#"{"control_id":"c1","peso_total":-1.5}"#, "peso_total deve ser maior que zero"),
+            ("weighing", r#"{"control_id":"c1","peso_total":3,"peso_tara":-1}"#, "peso_tara não pode ser negativo"),
+        ] {
+            assert_eq!(invalid_reason(kind, payload), reason, "{} {}", kind, payload);
+        }
+
+        let weighing = op("weighing", r#"{"control_id":"c1","peso_total":12.5,"peso_tara":0}"#);
+        assert_eq!(weighing.payload_json(), r#"{"control_id":"c1","peso_tara":0.0,"peso_total":12.5}"#);
+    }
+
+    #[test]
+    fn distribute_merges_overlapping_reads_for_the_same_bed() {
+        let first = op("distribute", r#"{"tags":["AA01","AA02"],"bedId":"b1"}"#);
+
+        let reread = op("distribute", r#"{"tags":["aa-02","AA01"],"bedId":"b1"}"#);
+        assert_eq!(first.coalesce(&reread), Some(Coalesce::Drop));
+
+        let more = op("distribute", r#"{"tags":["AA02","AA03"],"bedId":"b1"}"#);
+        assert_eq!(
+            first.coalesce(&more),
+            Some(Coalesce::Merge(op("distribute", r#"{"tags":["AA01","AA02","AA03"],"bedId":"b1"}"#)))
+        );
+
+        let other_bed = op("distribute", r#"{"tags":["AA01"],"bedId":"b2"}"#);
+        assert_eq!(first.coalesce(&other_bed), None);
+        let other_cart = op("distribute", r#"{"tags":["BB01"],"bedId":"b1"}"#);
+        assert_eq!(first.coalesce(&other_cart), None);
+    }
+
+    #[test]
+    fn reception_drops_repeated_reads() {
+        let first = op("reception", r#"{"rfidTagUid":"AA01","sectorId":"lavanderia"}"#);
+        let again = op("reception", r#"{"rfidTagUid":"AA01","sectorId":"lavanderia"}"#);
+        assert_eq!(first.coalesce(&again), Some(Coalesce::Drop));
+
+        let other_sector = op("reception", r#"{"rfidTagUid":"AA01","sectorId":"uti"}"#);
+        assert_eq!(first.coalesce(&other_sector), None);
+        let other_tag = op("reception", r#"{"rfidTagUid":"AA02","sectorId":"lavanderia"}"#);
+        assert_eq!(first.coalesce(&other_tag), None);
+    }
+
+    #[test]
+    fn retire_keeps_the_first_request_per_tag() {
+        let first = op("retire", r#"{"tag":"AA01","reason":"rasgado"}"#);
+        let again = op("retire", r#"{"tag":"AA01","reason":"manchado","releaseTag":true}"#);
+        assert_eq!(first.coalesce(&again), Some(Coalesce::Drop));
+
+        let other = op("retire", r#"{"tag":"AA02","reason":"rasgado"}"#);
+        assert_eq!(first.coalesce(&other), None);
+    }
+
+    #[test]
+    fn associate_merges_tags_for_the_same_item_and_batch() {
+        let first = op("associate", r#"{"tags":["AA01","AA02"],"itemId":"lencol","batchNumber":7}"#);
+
+        let reread = op("associate", r#"{"tags":["AA02"],"itemId":"lencol","batchNumber":7}"#);
+        assert_eq!(first.coalesce(&reread), Some(Coalesce::Drop));
+
+        let more = op("associate", r#"{"tags":["AA02","AA03"],"itemId":"lencol","batchNumber":7}"#);
+        assert_eq!(
+            first.coalesce(&more),
+            Some(Coalesce::Merge(op(
+                "associate",
+                r#"{"tags":["AA01","AA02","AA03"],"itemId":"lencol","batchNumber":7}"#
+            )))
+        );
+
+        let other_batch = op("associate", r#"{"tags":["AA01"],"itemId":"lencol","batchNumber":8}"#);
+        assert_eq!(first.coalesce(&other_batch), None);
+    }
+
+    #[test]
+    fn replace_tag_drops_identical_swaps() {
+        let first = op("replace_tag", r#"{"oldTag":"AA01","newTag":"BB01"}"#);
+        let again = op("replace_tag", r#"{"oldTag":"AA01","newTag":"BB01","notes":"relido"}"#);
+        assert_eq!(first.coalesce(&again), Some(Coalesce::Drop));
+
+        let other = op("replace_tag", r#"{"oldTag":"AA01","newTag":"BB02"}"#);
+        assert_eq!(first.coalesce(&other), None);
+    }
+
+    #[test]
+    fn detach_tag_drops_repeated_requests() {
+        let first = op("detach_tag", r#"{"tag":"AA01"}"#);
+        assert_eq!(first.coalesce(&op("detach_tag", r#"{"tag":"aa:01"}"#)), Some(Coalesce::Drop));
+        assert_eq!(first.coalesce(&op("detach_tag", r#"{"tag":"AA02"}"#)), None);
+    }
+
+    #[test]
+    fn nonconformity_drops_only_same_reason() {
+        let first = op("nonconformity", r#"{"tag":"AA01","reason":"mancha"}"#);
+        let again = op("nonconformity", r#"{"tag":"AA01","reason":"mancha"}"#);
+        assert_eq!(first.coalesce(&again), Some(Coalesce::Drop));
+
+        let other_reason = op("nonconformity", r#"{"tag":"AA01","reason":"furo"}"#);
+        assert_eq!(first.coalesce(&other_reason), None);
+    }
+
+    #[test]
+    fn weighings_are_never_coalesced() {
+        let first = op("weighing", r#"{"control_id":"c1","peso_total":12.5,"cage_id":"g1"}"#);
+        let same = op("weighing", r#"{"control_id":"c1","peso_total":12.5,"cage_id":"g1"}"#);
+        assert_eq!(first.coalesce(&same), None);
+    }
+
+    #[test]
+    fn different_types_are_never_coalesced() {
+        let retire = op("retire", r#"{"tag":"AA01","reason":"rasgado"}"#);
+        let detach = op("detach_tag", r#"{"tag":"AA01"}"#);
+        assert_eq!(retire.coalesce(&detach), None);
+    }
+}
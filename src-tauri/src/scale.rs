@@ -0,0 +1,815 @@
+use crate::scale_protocol::{self, ScaleProtocol};
+use crate::scale_stability::{StabilityConfig, StabilityDetector};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+// Modelos de balança conhecidos (mesmos ids/labels de scaleDrivers.ts)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScaleModel {
+    Digitron300,
+    ToledoPrix,
+    RicelakeX,
+    CustomHlProtocol,
+}
+
+impl ScaleModel {
+    // Aceita o id do driver ou o label exibido nas configurações
+    pub fn from_name(name: &str) -> Option<ScaleModel> {
+        match name.trim() {
+            "digitron-300" | "Digitron 300kg" => Some(ScaleModel::Digitron300),
+            "toledo-prix" | "Toledo Prix" => Some(ScaleModel::ToledoPrix),
+            "ricelake-x" | "Rice Lake X" => Some(ScaleModel::RicelakeX),
+            "custom-hl-protocol" | "Balança Genérica (Protocolo H/L)" => {
+                Some(ScaleModel::CustomHlProtocol)
+            }
+            _ => None,
+        }
+    }
+
+    // Configuração de linha de fábrica de cada modelo (ScaleDriver.defaults)
+    fn line_defaults(self) -> (u32, Parity) {
+        match self {
+            ScaleModel::ToledoPrix => (4800, Parity::Even),
+            ScaleModel::Digitron300 | ScaleModel::RicelakeX | ScaleModel::CustomHlProtocol => {
+                (9600, Parity::None)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlowControl {
+    None,
+    Software,
+    Hardware,
+}
+
+// Configuração da porta serial como vem das configurações da UI (scale em
+// useSettings). Campos ausentes usam o padrão do modelo e depois 9600/8N1.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerialConfig {
+    pub port: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub baud_rate: Option<u32>,
+    #[serde(default)]
+    pub data_bits: Option<u8>,
+    #[serde(default)]
+    pub parity: Option<Parity>,
+    #[serde(default)]
+    pub stop_bits: Option<f32>,
+    #[serde(default)]
+    pub flow_control: Option<FlowControl>,
+    #[serde(default)]
+    pub read_timeout_ms: Option<u64>,
+}
+
+// Configuração de linha já resolvida e validada
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SerialSettings {
+    pub port: String,
+    pub baud_rate: u32,
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: u8,
+    pub flow_control: FlowControl,
+    pub read_timeout_ms: u64,
+}
+
+// Curto para o laço de leitura também publicar o último peso sem atraso
+const DEFAULT_READ_TIMEOUT_MS: u64 = 250;
+
+impl SerialConfig {
+    pub fn model(&self) -> Option<ScaleModel> {
+        self.model.as_deref().and_then(ScaleModel::from_name)
+    }
+
+    pub fn resolve(&self) -> Result<SerialSettings, ScaleError> {
+        let invalid = |reason: String| Err(ScaleError::InvalidConfig(reason));
+
+        if self.port.trim().is_empty() {
+            return invalid("porta serial não informada".into());
+        }
+        let (default_baud, default_parity) = self
+            .model()
+            .map(ScaleModel::line_defaults)
+            .unwrap_or((9600, Parity::None));
+
+        let baud_rate = self.baud_rate.unwrap_or(default_baud);
+        if baud_rate == 0 {
+            return invalid("baud rate deve ser maior que zero".into());
+        }
+        let data_bits = self.data_bits.unwrap_or(8);
+        if !(5..=8).contains(&data_bits) {
+            return invalid(format!("data bits {} não suportado (5 a 8)", data_bits));
+        }
+        let stop_bits = match self.stop_bits {
+            None => 1,
+            Some(1.0) => 1,
+            Some(2.0) => 2,
+            Some(bits) => return invalid(format!("stop bits {} não suportado (1 ou 2)", bits)),
+        };
+
+        Ok(SerialSettings {
+            port: self.port.trim().to_string(),
+            baud_rate,
+            data_bits,
+            parity: self.parity.unwrap_or(default_parity),
+            stop_bits,
+            flow_control: self.flow_control.unwrap_or(FlowControl::None),
+            read_timeout_ms: self.read_timeout_ms.unwrap_or(DEFAULT_READ_TIMEOUT_MS).max(1),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScaleError {
+    InvalidConfig(String),
+    PortNotFound(String),
+    PortBusy(String),
+    PermissionDenied(String),
+    Io { port: String, message: String },
+}
+
+impl fmt::Display for ScaleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScaleError::InvalidConfig(reason) => {
+                write!(f, "Configuração da balança inválida: {}", reason)
+            }
+            ScaleError::PortNotFound(port) => {
+                write!(f, "Porta {} não encontrada. Verifique o cabo e a porta configurada", port)
+            }
+            ScaleError::PortBusy(port) => {
+                write!(f, "Porta {} em uso por outro programa", port)
+            }
+            ScaleError::PermissionDenied(port) => {
+                write!(f, "Sem permissão para abrir a porta {} (usuário no grupo dialout?)", port)
+            }
+            ScaleError::Io { port, message } => {
+                write!(f, "Erro na porta {}: {}", port, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScaleError {}
+
+impl ScaleError {
+    fn from_serial(port: &str, e: serialport::Error) -> ScaleError {
+        let port = port.to_string();
+        match e.kind() {
+            serialport::ErrorKind::NoDevice | serialport::ErrorKind::Io(io::ErrorKind::NotFound) => {
+                ScaleError::PortNotFound(port)
+            }
+            serialport::ErrorKind::Io(io::ErrorKind::PermissionDenied) => {
+                ScaleError::PermissionDenied(port)
+            }
+            // EBUSY não tem ErrorKind próprio no serialport
+            _ if e.description.to_lowercase().contains("busy") => ScaleError::PortBusy(port),
+            serialport::ErrorKind::InvalidInput => ScaleError::InvalidConfig(e.description),
+            _ => ScaleError::Io {
+                port,
+                message: e.description,
+            },
+        }
+    }
+}
+
+// Abre a porta com a configuração de linha explícita (a porta é aberta em
+// modo exclusivo: uma segunda leitura na mesma porta falha como "em uso")
+pub fn open_serial_port(
+    settings: &SerialSettings,
+) -> Result<Box<dyn serialport::SerialPort>, ScaleError> {
+    let data_bits = match settings.data_bits {
+        5 => serialport::DataBits::Five,
+        6 => serialport::DataBits::Six,
+        7 => serialport::DataBits::Seven,
+        _ => serialport::DataBits::Eight,
+    };
+    let parity = match settings.parity {
+        Parity::None => serialport::Parity::None,
+        Parity::Even => serialport::Parity::Even,
+        Parity::Odd => serialport::Parity::Odd,
+    };
+    let stop_bits = match settings.stop_bits {
+        2 => serialport::StopBits::Two,
+        _ => serialport::StopBits::One,
+    };
+    let flow_control = match settings.flow_control {
+        FlowControl::None => serialport::FlowControl::None,
+        FlowControl::Software => serialport::FlowControl::Software,
+        FlowControl::Hardware => serialport::FlowControl::Hardware,
+    };
+
+    serialport::new(&settings.port, settings.baud_rate)
+        .data_bits(data_bits)
+        .parity(parity)
+        .stop_bits(stop_bits)
+        .flow_control(flow_control)
+        .timeout(Duration::from_millis(settings.read_timeout_ms))
+        .open()
+        .map_err(|e| ScaleError::from_serial(&settings.port, e))
+}
+
+// Separa o fluxo da balança em quadros terminados por CR e/ou LF
+#[derive(Default)]
+pub struct FrameReader {
+    buffer: Vec<u8>,
+}
+
+const MAX_FRAME_LEN: usize = 256;
+
+impl FrameReader {
+    // Lê o que houver na porta e devolve os quadros completos. Timeout de
+    // leitura não é erro: só significa que não chegou nada no intervalo.
+    pub fn read_frames<R: Read + ?Sized>(&mut self, source: &mut R) -> io::Result<Vec<String>> {
+        let mut chunk = [0u8; 128];
+        let read = match source.read(&mut chunk) {
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "porta fechada")),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => return Ok(Vec::new()),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut frames = Vec::new();
+        for &byte in &chunk[..read] {
+            if byte == b'\r' || byte == b'\n' {
+                if !self.buffer.is_empty() {
+                    frames.push(String::from_utf8_lossy(&self.buffer).trim().to_string());
+                    self.buffer.clear();
+                }
+            } else if self.buffer.len() < MAX_FRAME_LEN {
+                self.buffer.push(byte);
+            } else {
+                // Lixo sem terminador (baud rate errado?): descarta
+                self.buffer.clear();
+            }
+        }
+        frames.retain(|frame| !frame.is_empty());
+        Ok(frames)
+    }
+}
+
+// Estado atual da balança: payload do evento scale-weight e retorno de
+// read_scale_weight
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ScaleSnapshot {
+    pub weight: f64,
+    pub stable: bool,
+    pub overload: bool,
+    pub connected: bool,
+    pub timestamp: i64,
+}
+
+// Limita a taxa de eventos scale-weight: mudança de estado (estável,
+// conectado, sobrecarga) sai na hora, mudança de peso no máximo a cada
+// `interval_ms` e, parado, um heartbeat a cada `heartbeat_ms`.
+pub struct WeightThrottle {
+    interval_ms: i64,
+    heartbeat_ms: i64,
+    last: Option<ScaleSnapshot>,
+}
+
+impl Default for WeightThrottle {
+    fn default() -> Self {
+        Self {
+            interval_ms: 200,
+            heartbeat_ms: 2000,
+            last: None,
+        }
+    }
+}
+
+impl WeightThrottle {
+    // Chamado a cada volta do laço de leitura com o estado atual; true se
+    // deve ser publicado
+    pub fn offer(&mut self, snapshot: &ScaleSnapshot) -> bool {
+        let emit = match &self.last {
+            None => true,
+            Some(last) => {
+                let elapsed = snapshot.timestamp - last.timestamp;
+                let state_changed = last.stable != snapshot.stable
+                    || last.connected != snapshot.connected
+                    || last.overload != snapshot.overload;
+                let weight_changed = (last.weight - snapshot.weight).abs() >= 0.001;
+                state_changed
+                    || (weight_changed && elapsed >= self.interval_ms)
+                    || elapsed >= self.heartbeat_ms
+            }
+        };
+        if emit {
+            self.last = Some(snapshot.clone());
+        }
+        emit
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleConnectionState {
+    Stopped,
+    Connected,
+    Disconnected,
+    Error,
+}
+
+// Payload do evento scale-status
+#[derive(Debug, Clone, Serialize)]
+pub struct ScaleStatus {
+    pub state: ScaleConnectionState,
+    pub port: Option<String>,
+    pub protocol: Option<String>,
+    pub error: Option<String>,
+    pub timestamp: i64,
+}
+
+impl Default for ScaleStatus {
+    fn default() -> Self {
+        Self {
+            state: ScaleConnectionState::Stopped,
+            port: None,
+            protocol: None,
+            error: None,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        }
+    }
+}
+
+// Intervalo entre tentativas de reabrir a porta depois de uma falha
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+// Controla o único leitor ativo da balança. Um novo start encerra o leitor
+// anterior e espera a thread soltar a porta antes de abrir a nova.
+#[derive(Default)]
+pub struct ScaleController {
+    // Serializa os starts: dois starts simultâneos não abrem a porta ao mesmo tempo
+    starting: Mutex<()>,
+    reader: Mutex<Option<ScaleReader>>,
+    last_config: Mutex<Option<(SerialConfig, StabilityConfig)>>,
+    snapshot: Arc<Mutex<ScaleSnapshot>>,
+    status: Arc<Mutex<ScaleStatus>>,
+}
+
+struct ScaleReader {
+    tx: Sender<ScaleCommand>,
+    handle: JoinHandle<()>,
+}
+
+enum ScaleCommand {
+    Stop,
+}
+
+impl ScaleController {
+    pub fn start(
+        &self,
+        app_handle: &AppHandle,
+        config: SerialConfig,
+        stability: StabilityConfig,
+    ) -> Result<ScaleStatus, String> {
+        self.swap_reader(|| {
+            *self.last_config.lock().unwrap() = Some((config.clone(), stability.clone()));
+
+            let protocol = scale_protocol::protocol_for(config.model());
+            let opened = config
+                .resolve()
+                .and_then(|settings| open_serial_port(&settings).map(|port| (settings, port)));
+            let (settings, port) = match opened {
+                Ok(opened) => opened,
+                Err(e) => {
+                    publish_status(
+                        app_handle,
+                        &self.status,
+                        ScaleStatus {
+                            state: ScaleConnectionState::Error,
+                            port: Some(config.port.clone()),
+                            protocol: Some(protocol.name().to_string()),
+                            error: Some(e.to_string()),
+                            ..Default::default()
+                        },
+                    );
+                    return Err(e.to_string());
+                }
+            };
+
+            let (tx, rx) = mpsc::channel();
+            let worker = ScaleWorker {
+                app_handle: app_handle.clone(),
+                settings,
+                protocol,
+                detector: StabilityDetector::new(stability),
+                throttle: WeightThrottle::default(),
+                snapshot: Arc::clone(&self.snapshot),
+                status: Arc::clone(&self.status),
+                rx,
+            };
+            worker.publish_status(ScaleConnectionState::Connected, None);
+            let status = self.status();
+            let reader = ScaleReader {
+                tx,
+                handle: thread::spawn(move || worker.run(port)),
+            };
+            Ok((reader, status))
+        })
+    }
+
+    // Encerra o leitor ativo e instala o que `open` criar. O leitor anterior
+    // sai sob o lock e o join acontece fora dele: stop e outros starts não
+    // ficam presos enquanto a thread antiga solta a porta.
+    fn swap_reader<T>(
+        &self,
+        open: impl FnOnce() -> Result<(ScaleReader, T), String>,
+    ) -> Result<T, String> {
+        let _starting = self.starting.lock().unwrap();
+        let previous = self.reader.lock().unwrap().take();
+        stop_reader(previous);
+
+        let (reader, result) = open()?;
+        *self.reader.lock().unwrap() = Some(reader);
+        Ok(result)
+    }
+
+    // Para a leitura e espera a thread liberar a porta. Um start em andamento
+    // termina antes: senão o leitor novo seria instalado depois do stop.
+    pub fn stop(&self) {
+        let _starting = self.starting.lock().unwrap();
+        let reader = self.reader.lock().unwrap().take();
+        stop_reader(reader);
+    }
+
+    // Reabre com a última configuração usada no start
+    pub fn restart(&self, app_handle: &AppHandle) -> Result<ScaleStatus, String> {
+        let Some((config, stability)) = self.last_config.lock().unwrap().clone() else {
+            return Err("Leitura da balança não iniciada".into());
+        };
+        self.start(app_handle, config, stability)
+    }
+
+    pub fn status(&self) -> ScaleStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn snapshot(&self) -> ScaleSnapshot {
+        self.snapshot.lock().unwrap().clone()
+    }
+}
+
+fn stop_reader(reader: Option<ScaleReader>) {
+    if let Some(reader) = reader {
+        let _ = reader.tx.send(ScaleCommand::Stop);
+        let _ = reader.handle.join();
+    }
+}
+
+fn publish_status(app_handle: &AppHandle, shared: &Mutex<ScaleStatus>, status: ScaleStatus) {
+    *shared.lock().unwrap() = status.clone();
+    let _ = app_handle.emit_all("scale-status", status);
+}
+
+struct ScaleWorker {
+    app_handle: AppHandle,
+    settings: SerialSettings,
+    protocol: Box<dyn ScaleProtocol>,
+    detector: StabilityDetector,
+    throttle: WeightThrottle,
+    snapshot: Arc<Mutex<ScaleSnapshot>>,
+    status: Arc<Mutex<ScaleStatus>>,
+    rx: Receiver<ScaleCommand>,
+}
+
+impl ScaleWorker {
+    // Laço de leitura. O timeout de leitura da porta limita quanto tempo um
+    // Stop espera para ser atendido.
+    fn run(mut self, port: Box<dyn serialport::SerialPort>) {
+        let mut port = Some(port);
+        let mut frames = FrameReader::default();
+
+        loop {
+            let Some(open) = port.as_mut() else {
+                if self.stop_requested(RECONNECT_INTERVAL) {
+                    break;
+                }
+                match open_serial_port(&self.settings) {
+                    Ok(reopened) => {
+                        port = Some(reopened);
+                        frames = FrameReader::default();
+                        self.publish_status(ScaleConnectionState::Connected, None);
+                    }
+                    Err(e) => self.publish_status(ScaleConnectionState::Disconnected, Some(e)),
+                }
+                continue;
+            };
+            if self.stop_requested(Duration::ZERO) {
+                break;
+            }
+
+            match frames.read_frames(open) {
+                Ok(lines) => self.handle_frames(&lines),
+                Err(e) => {
+                    // Cabo desconectado ou adaptador USB removido: fecha e tenta reabrir
+                    port = None;
+                    let error = ScaleError::Io {
+                        port: self.settings.port.clone(),
+                        message: e.to_string(),
+                    };
+                    self.publish_status(ScaleConnectionState::Disconnected, Some(error));
+                    self.publish_snapshot(|current| {
+                        current.connected = false;
+                        current.stable = false;
+                    });
+                }
+            }
+        }
+
+        drop(port);
+        self.publish_snapshot(|current| {
+            current.connected = false;
+            current.stable = false;
+        });
+        self.publish_status(ScaleConnectionState::Stopped, None);
+    }
+
+    fn handle_frames(&mut self, lines: &[String]) {
+        let readings: Vec<_> = lines.iter().filter_map(|data| self.protocol.parse(data)).collect();
+        let now = chrono::Utc::now().timestamp_millis();
+        for reading in &readings {
+            for event in self.detector.push(reading, now) {
+                let _ = self.app_handle.emit_all(event.event_name(), &event);
+            }
+        }
+        // Publica a cada volta (inclusive timeout) para o throttle liberar o
+        // último peso retido
+        let stable = self.detector.is_stable();
+        self.publish_snapshot(|current| {
+            if let Some(reading) = readings.last() {
+                if !reading.overload {
+                    current.weight = reading.weight_kg();
+                }
+                current.overload = reading.overload;
+            }
+            current.stable = stable;
+            current.connected = true;
+        });
+    }
+
+    fn stop_requested(&self, wait: Duration) -> bool {
+        match self.rx.recv_timeout(wait) {
+            Ok(ScaleCommand::Stop) | Err(RecvTimeoutError::Disconnected) => true,
+            Err(RecvTimeoutError::Timeout) => false,
+        }
+    }
+
+    // Atualiza o estado compartilhado e publica scale-weight se o throttle deixar
+    fn publish_snapshot(&mut self, update: impl FnOnce(&mut ScaleSnapshot)) {
+        let current = {
+            let mut snapshot = self.snapshot.lock().unwrap();
+            update(&mut snapshot);
+            snapshot.timestamp = chrono::Utc::now().timestamp_millis();
+            snapshot.clone()
+        };
+        if self.throttle.offer(&current) {
+            let _ = self.app_handle.emit_all("scale-weight", current);
+        }
+    }
+
+    fn publish_status(&self, state: ScaleConnectionState, error: Option<ScaleError>) {
+        let error = error.map(|e| e.to_string());
+        // Falhas repetidas ao reabrir a porta não geram um evento a cada tentativa
+        {
+            let current = self.status.lock().unwrap();
+            if current.state == state
+                && current.error == error
+                && current.port.as_deref() == Some(self.settings.port.as_str())
+            {
+                return;
+            }
+        }
+        publish_status(
+            &self.app_handle,
+            &self.status,
+            ScaleStatus {
+                state,
+                port: Some(self.settings.port.clone()),
+                protocol: Some(self.protocol.name().to_string()),
+                error,
+                ..Default::default()
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn config(model: &str) -> SerialConfig {
+        SerialConfig {
+            port: "/dev/ttyUSB0".into(),
+            model: Some(model.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn line_settings_follow_driver_defaults() {
+        let toledo = config("Toledo Prix").resolve().unwrap();
+        assert_eq!((toledo.baud_rate, toledo.parity), (4800, Parity::Even));
+
+        let digitron = config("digitron-300").resolve().unwrap();
+        assert_eq!((digitron.baud_rate, digitron.parity), (9600, Parity::None));
+        assert_eq!((digitron.data_bits, digitron.stop_bits), (8, 1));
+
+        let overridden = SerialConfig {
+            baud_rate: Some(2400),
+            parity: Some(Parity::Odd),
+            stop_bits: Some(2.0),
+            flow_control: Some(FlowControl::Hardware),
+            ..config("Toledo Prix")
+        }
+        .resolve()
+        .unwrap();
+        assert_eq!(overridden.baud_rate, 2400);
+        assert_eq!(overridden.parity, Parity::Odd);
+        assert_eq!(overridden.stop_bits, 2);
+        assert_eq!(overridden.flow_control, FlowControl::Hardware);
+
+        let settings: SerialConfig = serde_json::from_str(
+            r#"{"port":"/dev/ttyS0","model":"Balança Genérica (Protocolo H/L)","baudRate":9600,"dataBits":8,"parity":"none","stopBits":1}"#,
+        )
+        .unwrap();
+        assert_eq!(settings.model(), Some(ScaleModel::CustomHlProtocol));
+        assert!(settings.resolve().is_ok());
+    }
+
+    #[test]
+    fn rejects_unsupported_line_settings() {
+        let half_stop = SerialConfig {
+            stop_bits: Some(1.5),
+            ..config("Toledo Prix")
+        };
+        assert!(matches!(half_stop.resolve(), Err(ScaleError::InvalidConfig(_))));
+
+        let no_port = SerialConfig::default();
+        assert!(matches!(no_port.resolve(), Err(ScaleError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn missing_port_is_reported_as_not_found() {
+        let settings = SerialConfig {
+            port: "/dev/ttyBALANCA_INEXISTENTE".into(),
+            ..Default::default()
+        }
+        .resolve()
+        .unwrap();
+        match open_serial_port(&settings) {
+            Err(e) => {
+                assert_eq!(e, ScaleError::PortNotFound(settings.port.clone()));
+                assert!(e.to_string().contains("não encontrada"));
+            }
+            Ok(_) => panic!("porta inexistente não deveria abrir"),
+        }
+    }
+
+    #[test]
+    fn splits_frames_across_reads_and_ignores_timeouts() {
+        struct Chunks(Vec<io::Result<Vec<u8>>>);
+        impl Read for Chunks {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let bytes = self.0.remove(0)?;
+                buf[..bytes.len()].copy_from_slice(&bytes);
+                Ok(bytes.len())
+            }
+        }
+
+        let mut source = Chunks(vec![
+            Ok(b"H0000.15\r\nL00".to_vec()),
+            Err(io::Error::new(io::ErrorKind::TimedOut, "timeout")),
+            Ok(b"00.10\r\n\r\n".to_vec()),
+            Ok(Vec::new()),
+        ]);
+        let mut frames = FrameReader::default();
+
+        assert_eq!(frames.read_frames(&mut source).unwrap(), vec!["H0000.15"]);
+        assert!(frames.read_frames(&mut source).unwrap().is_empty());
+        assert_eq!(frames.read_frames(&mut source).unwrap(), vec!["L0000.10"]);
+        assert_eq!(
+            frames.read_frames(&mut source).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn throttles_weight_changes_but_not_state_changes() {
+        let mut throttle = WeightThrottle::default();
+        let at = |timestamp: i64, weight: f64, stable: bool| ScaleSnapshot {
+            weight,
+            stable,
+            overload: false,
+            connected: true,
+            timestamp,
+        };
+
+        assert!(throttle.offer(&at(0, 1.0, false)));
+        assert!(!throttle.offer(&at(50, 2.0, false)));
+        assert!(!throttle.offer(&at(150, 3.0, false)));
+        // Último valor sai assim que o intervalo passa, mesmo sem quadro novo
+        assert!(throttle.offer(&at(250, 3.0, false)));
+        assert!(!throttle.offer(&at(300, 3.0, false)));
+        // Ficou estável: publica na hora
+        assert!(throttle.offer(&at(320, 3.0, true)));
+        // Parado: só o heartbeat
+        assert!(!throttle.offer(&at(2000, 3.0, true)));
+        assert!(throttle.offer(&at(2320, 3.0, true)));
+        // Desconectou: publica na hora
+        assert!(throttle.offer(&ScaleSnapshot {
+            connected: false,
+            ..at(2330, 3.0, false)
+        }));
+    }
+
+    // Leitor de teste: atende o Stop depois de `stop_delay` e marca `stopped`
+    fn fake_reader(stop_delay: Duration) -> (ScaleReader, Arc<AtomicBool>) {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stopped);
+        let (tx, rx) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let _ = rx.recv();
+            thread::sleep(stop_delay);
+            flag.store(true, Ordering::SeqCst);
+        });
+        (ScaleReader { tx, handle }, stopped)
+    }
+
+    #[test]
+    fn second_start_stops_the_first_reader_and_stop_cancels_the_worker() {
+        let controller = ScaleController::default();
+        let (first, first_stopped) = fake_reader(Duration::ZERO);
+        controller.swap_reader(|| Ok((first, ()))).unwrap();
+        assert!(!first_stopped.load(Ordering::SeqCst));
+
+        let (second, second_stopped) = fake_reader(Duration::ZERO);
+        controller.swap_reader(|| Ok((second, ()))).unwrap();
+        assert!(first_stopped.load(Ordering::SeqCst));
+        assert!(!second_stopped.load(Ordering::SeqCst));
+
+        // Falha ao abrir a porta nova: o leitor anterior já foi encerrado
+        let error = controller
+            .swap_reader::<()>(|| Err("Porta em uso".into()))
+            .unwrap_err();
+        assert_eq!(error, "Porta em uso");
+        assert!(second_stopped.load(Ordering::SeqCst));
+        assert!(controller.reader.lock().unwrap().is_none());
+
+        let (third, third_stopped) = fake_reader(Duration::ZERO);
+        controller.swap_reader(|| Ok((third, ()))).unwrap();
+        controller.stop();
+        assert!(third_stopped.load(Ordering::SeqCst));
+        assert!(controller.reader.lock().unwrap().is_none());
+        controller.stop();
+    }
+
+    #[test]
+    fn stop_during_start_leaves_no_reader_running() {
+        let controller = Arc::new(ScaleController::default());
+        let (slow, slow_stopped) = fake_reader(Duration::from_millis(300));
+        controller.swap_reader(|| Ok((slow, ()))).unwrap();
+
+        let (next, next_stopped) = fake_reader(Duration::ZERO);
+        let starting = {
+            let controller = Arc::clone(&controller);
+            thread::spawn(move || controller.swap_reader(|| Ok((next, ()))).unwrap())
+        };
+        thread::sleep(Duration::from_millis(100));
+
+        // O start espera o leitor lento sem segurar o lock do leitor
+        assert!(controller.reader.try_lock().is_ok());
+        assert!(!slow_stopped.load(Ordering::SeqCst));
+
+        // O stop espera o start terminar e encerra o leitor que ele instalou
+        controller.stop();
+        assert!(slow_stopped.load(Ordering::SeqCst));
+        assert!(next_stopped.load(Ordering::SeqCst));
+        assert!(controller.reader.lock().unwrap().is_none());
+        starting.join().unwrap();
+    }
+}
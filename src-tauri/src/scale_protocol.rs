@@ -0,0 +1,299 @@
+use crate::scale::ScaleModel;
+use serde::Serialize;
+
+// Leitura já decodificada de um quadro da balança. `stable` é None quando o
+// protocolo não informa estabilidade (H/L); em sobrecarga o peso vem zerado.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScaleReading {
+    pub weight: f64,
+    pub unit: String,
+    pub stable: Option<bool>,
+    pub overload: bool,
+    pub raw: String,
+}
+
+impl ScaleReading {
+    // Peso convertido para kg (unidade usada nas pesagens)
+    pub fn weight_kg(&self) -> f64 {
+        match self.unit.as_str() {
+            "g" => self.weight / 1000.0,
+            "lb" => self.weight * 0.453_592_37,
+            _ => self.weight,
+        }
+    }
+
+    fn overload(unit: &str, raw: &str) -> ScaleReading {
+        ScaleReading {
+            weight: 0.0,
+            unit: normalize_unit(unit),
+            stable: Some(false),
+            overload: true,
+            raw: raw.to_string(),
+        }
+    }
+}
+
+// Decodifica um quadro (sem CR/LF) no formato de um modelo de balança.
+// Quadros que não são de peso (eco de comando, lixo de linha) retornam None.
+pub trait ScaleProtocol: Send {
+    fn name(&self) -> &'static str;
+    fn parse(&self, frame: &str) -> Option<ScaleReading>;
+}
+
+// Protocolo do modelo configurado; sem modelo conhecido mantém o H/L, que era
+// o único formato lido antes
+pub fn protocol_for(model: Option<ScaleModel>) -> Box<dyn ScaleProtocol> {
+    match model {
+        Some(ScaleModel::Digitron300) => Box::new(DigitronProtocol),
+        Some(ScaleModel::ToledoPrix) => Box::new(ToledoProtocol),
+        Some(ScaleModel::RicelakeX) => Box::new(RiceLakeJsonProtocol),
+        Some(ScaleModel::CustomHlProtocol) | None => Box::new(HlProtocol),
+    }
+}
+
+fn normalize_unit(unit: &str) -> String {
+    match unit.trim().to_lowercase().as_str() {
+        "" | "kg" | "kgs" => "kg".to_string(),
+        "g" | "gr" => "g".to_string(),
+        "lb" | "lbs" => "lb".to_string(),
+        other => other.to_string(),
+    }
+}
+
+// Aceita vírgula decimal e espaços de preenchimento ("  1,25")
+fn parse_number(value: &str) -> Option<f64> {
+    let value: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    value.replace(',', ".").parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+// Balança genérica: H0000.15 / L0000.10 (peso em kg, sem indicação de estabilidade)
+pub struct HlProtocol;
+
+impl ScaleProtocol for HlProtocol {
+    fn name(&self) -> &'static str {
+        "hl"
+    }
+
+    fn parse(&self, frame: &str) -> Option<ScaleReading> {
+        let frame = frame.trim();
+        let mut chars = frame.chars();
+        if !matches!(chars.next(), Some('H' | 'L' | 'h' | 'l')) {
+            return None;
+        }
+        let weight = parse_number(chars.as_str())?;
+        Some(ScaleReading {
+            weight,
+            unit: "kg".to_string(),
+            stable: None,
+            overload: false,
+            raw: frame.to_string(),
+        })
+    }
+}
+
+// Digitron: <sinal><ST|US|OL>,<peso><unidade>, ex. "+ST,1.23kg" / "+US,  0.80kg" / "+OL,kg"
+pub struct DigitronProtocol;
+
+impl ScaleProtocol for DigitronProtocol {
+    fn name(&self) -> &'static str {
+        "digitron"
+    }
+
+    fn parse(&self, frame: &str) -> Option<ScaleReading> {
+        let frame = frame.trim();
+        let (head, value) = frame.split_once(',')?;
+        let (negative, status) = match head.trim() {
+            h if h.starts_with('-') => (true, &h[1..]),
+            h if h.starts_with('+') => (false, &h[1..]),
+            h => (false, h),
+        };
+
+        let value = value.trim();
+        let split = value
+            .find(|c: char| c.is_ascii_alphabetic())
+            .unwrap_or(value.len());
+        let (number, unit) = value.split_at(split);
+
+        let stable = match status.to_uppercase().as_str() {
+            "ST" => true,
+            "US" => false,
+            "OL" => return Some(ScaleReading::overload(unit, frame)),
+            _ => return None,
+        };
+        let weight = parse_number(number)?;
+        Some(ScaleReading {
+            weight: if negative { -weight } else { weight },
+            unit: normalize_unit(unit),
+            stable: Some(stable),
+            overload: false,
+            raw: frame.to_string(),
+        })
+    }
+}
+
+// Toledo Prix: PESO:1.23;UN:KG;ST:OK (ST:INST em movimento, ST:SOB/OL em sobrecarga)
+pub struct ToledoProtocol;
+
+impl ScaleProtocol for ToledoProtocol {
+    fn name(&self) -> &'static str {
+        "toledo"
+    }
+
+    fn parse(&self, frame: &str) -> Option<ScaleReading> {
+        let frame = frame.trim();
+        let mut weight = None;
+        let mut unit = "kg";
+        let mut status = None;
+        for field in frame.split(';') {
+            let Some((key, value)) = field.split_once(':') else {
+                continue;
+            };
+            match key.trim().to_uppercase().as_str() {
+                "PESO" => weight = Some(value.trim()),
+                "UN" => unit = value.trim(),
+                "ST" => status = Some(value.trim().to_uppercase()),
+                _ => {}
+            }
+        }
+
+        let weight = weight?;
+        if matches!(status.as_deref(), Some("SOB" | "OL" | "SOBRECARGA")) {
+            return Some(ScaleReading::overload(unit, frame));
+        }
+        Some(ScaleReading {
+            weight: parse_number(weight)?,
+            unit: normalize_unit(unit),
+            stable: status.map(|s| s == "OK"),
+            overload: false,
+            raw: frame.to_string(),
+        })
+    }
+}
+
+// Rice Lake (modo JSON): {"weight":1.23,"unit":"kg","status":"OK"}
+// status OK = estável, MOTION = em movimento, OVERLOAD = sobrecarga
+pub struct RiceLakeJsonProtocol;
+
+impl ScaleProtocol for RiceLakeJsonProtocol {
+    fn name(&self) -> &'static str {
+        "ricelake-json"
+    }
+
+    fn parse(&self, frame: &str) -> Option<ScaleReading> {
+        let frame = frame.trim();
+        let value: serde_json::Value = serde_json::from_str(frame).ok()?;
+        let unit = value.get("unit").and_then(|u| u.as_str()).unwrap_or("kg");
+        let status = value
+            .get("status")
+            .and_then(|s| s.as_str())
+            .map(|s| s.trim().to_uppercase());
+
+        if status.as_deref().is_some_and(|s| s.starts_with("OVER") || s == "OL") {
+            return Some(ScaleReading::overload(unit, frame));
+        }
+        let weight = match value.get("weight")? {
+            serde_json::Value::Number(n) => n.as_f64()?,
+            serde_json::Value::String(s) => parse_number(s)?,
+            _ => return None,
+        };
+        Some(ScaleReading {
+            weight,
+            unit: normalize_unit(unit),
+            stable: status.map(|s| s == "OK"),
+            overload: false,
+            raw: frame.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(protocol: &dyn ScaleProtocol, frame: &str) -> ScaleReading {
+        protocol
+            .parse(frame)
+            .unwrap_or_else(|| panic!("{} não reconheceu {:?}", protocol.name(), frame))
+    }
+
+    #[test]
+    fn hl_frames() {
+        let hl = HlProtocol;
+        let high = reading(&hl, "H0000.15");
+        assert_eq!(high.weight, 0.15);
+        assert_eq!(high.unit, "kg");
+        assert_eq!(high.stable, None);
+        assert!(!high.overload);
+        assert_eq!(reading(&hl, "L0000.10").weight, 0.10);
+        assert_eq!(reading(&hl, "h0012.50").weight, 12.5);
+
+        assert!(hl.parse("H").is_none());
+        assert!(hl.parse("X0001.00").is_none());
+        assert!(hl.parse("H00?1.00").is_none());
+    }
+
+    #[test]
+    fn digitron_frames() {
+        let digitron = DigitronProtocol;
+        let stable = reading(&digitron, "+ST,1.23kg");
+        assert_eq!((stable.weight, stable.unit.as_str()), (1.23, "kg"));
+        assert_eq!(stable.stable, Some(true));
+        assert_eq!(stable.raw, "+ST,1.23kg");
+
+        let moving = reading(&digitron, "+US,  12.40kg");
+        assert_eq!((moving.weight, moving.stable), (12.4, Some(false)));
+        assert_eq!(reading(&digitron, "-ST,0.05kg").weight, -0.05);
+        assert_eq!(reading(&digitron, "+ST,1250g").weight_kg(), 1.25);
+
+        let overload = reading(&digitron, "+OL,kg");
+        assert!(overload.overload);
+        assert_eq!(overload.stable, Some(false));
+
+        // Eco do comando de leitura que a balança devolve antes do peso
+        assert!(digitron.parse("READ").is_none());
+        assert!(digitron.parse("+XX,1.00kg").is_none());
+    }
+
+    #[test]
+    fn toledo_frames() {
+        let toledo = ToledoProtocol;
+        let stable = reading(&toledo, "PESO:4.85;UN:KG;ST:OK");
+        assert_eq!((stable.weight, stable.unit.as_str()), (4.85, "kg"));
+        assert_eq!(stable.stable, Some(true));
+
+        let moving = reading(&toledo, "PESO:  0,85;UN:KG;ST:INST");
+        assert_eq!((moving.weight, moving.stable), (0.85, Some(false)));
+
+        let overload = reading(&toledo, "PESO:-----;UN:KG;ST:SOB");
+        assert!(overload.overload);
+
+        assert_eq!(reading(&toledo, "UN:G;PESO:500;ST:OK").weight_kg(), 0.5);
+        assert!(toledo.parse("UN:KG;ST:OK").is_none());
+        assert!(toledo.parse("PESO:abc;UN:KG;ST:OK").is_none());
+    }
+
+    #[test]
+    fn rice_lake_json_frames() {
+        let rice_lake = RiceLakeJsonProtocol;
+        let stable = reading(&rice_lake, r#"{"weight":3.42,"unit":"kg","status":"OK"}"#);
+        assert_eq!((stable.weight, stable.stable), (3.42, Some(true)));
+
+        let moving = reading(&rice_lake, r#"{"weight":"7.5","unit":"lb","status":"MOTION"}"#);
+        assert_eq!(moving.stable, Some(false));
+        assert!((moving.weight_kg() - 3.401_942_775).abs() < 1e-9);
+
+        let overload = reading(&rice_lake, r#"{"weight":0,"unit":"kg","status":"OVERLOAD"}"#);
+        assert!(overload.overload);
+
+        assert!(rice_lake.parse(r#"{"unit":"kg","status":"OK"}"#).is_none());
+        assert!(rice_lake.parse(r#"{"weight":3.4"#).is_none());
+    }
+
+    #[test]
+    fn protocol_follows_configured_model() {
+        assert_eq!(protocol_for(Some(ScaleModel::ToledoPrix)).name(), "toledo");
+        assert_eq!(protocol_for(Some(ScaleModel::Digitron300)).name(), "digitron");
+        assert_eq!(protocol_for(Some(ScaleModel::RicelakeX)).name(), "ricelake-json");
+        assert_eq!(protocol_for(None).name(), "hl");
+    }
+}
@@ -0,0 +1,275 @@
+use crate::scale_protocol::ScaleReading;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// Parâmetros de estabilidade. Os padrões reproduzem o que a UI fazia
+// (peso parado por 1,2 s) com uma tolerância para a oscilação da célula.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StabilityConfig {
+    // Janela em que o peso precisa ficar parado
+    pub window_ms: i64,
+    // Variação máxima (max - min) aceita dentro da janela, em kg
+    pub max_deviation_kg: f64,
+    // Abaixo disso não há gaiola na balança
+    pub min_weight_kg: f64,
+    // Peso considerado "balança vazia" para liberar a próxima captura
+    pub zero_band_kg: f64,
+    // Captura automática: um peso por gaiola colocada na balança
+    pub auto_capture: bool,
+}
+
+impl Default for StabilityConfig {
+    fn default() -> Self {
+        Self {
+            window_ms: 1200,
+            max_deviation_kg: 0.05,
+            min_weight_kg: 0.5,
+            zero_band_kg: 0.2,
+            auto_capture: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StabilityChange {
+    pub weight: f64,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CapturedWeight {
+    // Sequencial da captura desde o início da leitura
+    pub capture_id: u64,
+    pub weight: f64,
+    pub timestamp: i64,
+}
+
+// Serializa só o conteúdo: é o payload do evento `event_name()`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum StabilityEvent {
+    Stable(StabilityChange),
+    Unstable(StabilityChange),
+    Captured(CapturedWeight),
+    // Gaiola retirada (peso voltou para perto de zero) depois de uma captura
+    Removed { capture_id: u64, timestamp: i64 },
+}
+
+impl StabilityEvent {
+    // Nome do evento Tauri correspondente
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            StabilityEvent::Stable(_) => "scale-stable",
+            StabilityEvent::Unstable(_) => "scale-unstable",
+            StabilityEvent::Captured(_) => "scale-captured",
+            StabilityEvent::Removed { .. } => "scale-removed",
+        }
+    }
+}
+
+pub struct StabilityDetector {
+    config: StabilityConfig,
+    samples: VecDeque<(i64, f64)>,
+    stable: bool,
+    captures: u64,
+    // Captura da gaiola que ainda está na balança
+    holding: Option<u64>,
+}
+
+impl StabilityDetector {
+    pub fn new(config: StabilityConfig) -> Self {
+        Self {
+            config,
+            samples: VecDeque::new(),
+            stable: false,
+            captures: 0,
+            holding: None,
+        }
+    }
+
+    pub fn is_stable(&self) -> bool {
+        self.stable
+    }
+
+    // Alimenta uma leitura (timestamp em ms) e devolve as transições geradas
+    pub fn push(&mut self, reading: &ScaleReading, timestamp: i64) -> Vec<StabilityEvent> {
+        let mut events = Vec::new();
+        let weight = reading.weight_kg();
+
+        if reading.overload {
+            self.samples.clear();
+        } else {
+            self.samples.push_back((timestamp, weight));
+        }
+        // Mantém só a última amostra anterior à janela, que marca o início dela
+        while self.samples.len() > 1 && timestamp - self.samples[1].0 >= self.config.window_ms {
+            self.samples.pop_front();
+        }
+
+        let stable = !reading.overload
+            && reading.stable != Some(false)
+            && weight >= self.config.min_weight_kg
+            && self.window_is_still();
+
+        if stable != self.stable {
+            self.stable = stable;
+            let change = StabilityChange {
+                weight: if stable { self.window_mean() } else { weight },
+                timestamp,
+            };
+            events.push(if stable {
+                StabilityEvent::Stable(change)
+            } else {
+                StabilityEvent::Unstable(change)
+            });
+        }
+
+        if !self.config.auto_capture {
+            return events;
+        }
+        match self.holding {
+            None if stable => {
+                self.captures += 1;
+                self.holding = Some(self.captures);
+                events.push(StabilityEvent::Captured(CapturedWeight {
+                    capture_id: self.captures,
+                    weight: self.window_mean(),
+                    timestamp,
+                }));
+            }
+            Some(capture_id) if !reading.overload && weight.abs() <= self.config.zero_band_kg => {
+                self.holding = None;
+                events.push(StabilityEvent::Removed {
+                    capture_id,
+                    timestamp,
+                });
+            }
+            _ => {}
+        }
+        events
+    }
+
+    // A janela precisa estar completa e sem variação acima do limite
+    fn window_is_still(&self) -> bool {
+        let (Some(first), Some(last)) = (self.samples.front(), self.samples.back()) else {
+            return false;
+        };
+        if last.0 - first.0 < self.config.window_ms {
+            return false;
+        }
+        let (min, max) = self
+            .samples
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(min, max), &(_, w)| (min.min(w), max.max(w)));
+        max - min <= self.config.max_deviation_kg
+    }
+
+    fn window_mean(&self) -> f64 {
+        let sum: f64 = self.samples.iter().map(|&(_, w)| w).sum();
+        let mean = sum / self.samples.len().max(1) as f64;
+        (mean * 1000.0).round() / 1000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(weight: f64, stable: Option<bool>) -> ScaleReading {
+        ScaleReading {
+            weight,
+            unit: "kg".into(),
+            stable,
+            overload: false,
+            raw: String::new(),
+        }
+    }
+
+    // Alimenta uma leitura a cada 200 ms a partir de `start`
+    fn feed(
+        detector: &mut StabilityDetector,
+        start: i64,
+        weights: &[f64],
+        stable: Option<bool>,
+    ) -> Vec<StabilityEvent> {
+        weights
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &w)| detector.push(&reading(w, stable), start + i as i64 * 200))
+            .collect()
+    }
+
+    #[test]
+    fn becomes_stable_only_after_a_still_window() {
+        let mut detector = StabilityDetector::new(StabilityConfig::default());
+
+        let settling = feed(&mut detector, 0, &[3.0, 8.0, 12.1, 12.4, 12.42], None);
+        assert!(settling.is_empty());
+
+        let events = feed(&mut detector, 1000, &[12.41, 12.43, 12.42, 12.41, 12.42, 12.42], None);
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            StabilityEvent::Stable(change) => {
+                assert!((change.weight - 12.42).abs() < 0.01);
+                assert_eq!(change.timestamp, 1800);
+            }
+            other => panic!("esperava estável, veio {:?}", other),
+        }
+        assert!(detector.is_stable());
+
+        let events = feed(&mut detector, 2200, &[13.5], None);
+        assert!(matches!(events.as_slice(), [StabilityEvent::Unstable(_)]));
+    }
+
+    #[test]
+    fn respects_protocol_flag_and_minimum_weight() {
+        let mut detector = StabilityDetector::new(StabilityConfig::default());
+        let moving = feed(&mut detector, 0, &[5.0; 10], Some(false));
+        assert!(moving.is_empty(), "balança reportando movimento não é estável");
+
+        let events = feed(&mut detector, 2000, &[5.0], Some(true));
+        assert!(matches!(events.as_slice(), [StabilityEvent::Stable(_)]));
+
+        let mut detector = StabilityDetector::new(StabilityConfig::default());
+        assert!(feed(&mut detector, 0, &[0.1; 12], Some(true)).is_empty());
+    }
+
+    #[test]
+    fn auto_capture_yields_one_weight_per_cage() {
+        let mut detector = StabilityDetector::new(StabilityConfig {
+            auto_capture: true,
+            ..Default::default()
+        });
+        let captured = |events: &[StabilityEvent]| {
+            events
+                .iter()
+                .filter_map(|e| match e {
+                    StabilityEvent::Captured(c) => Some(c.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Primeira gaiola: estabiliza, alguém encosta (instável) e estabiliza de novo
+        let mut events = feed(&mut detector, 0, &[20.0; 8], None);
+        events.extend(feed(&mut detector, 1600, &[21.5, 20.0], None));
+        events.extend(feed(&mut detector, 2000, &[20.0; 8], None));
+        let first = captured(&events);
+        assert_eq!(first.len(), 1);
+        assert_eq!((first[0].capture_id, first[0].weight), (1, 20.0));
+
+        // Retirada: peso volta para perto de zero
+        let events = feed(&mut detector, 3600, &[6.0, 0.1], None);
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, StabilityEvent::Removed { capture_id: 1, .. })));
+
+        // Segunda gaiola gera a segunda captura
+        let events = feed(&mut detector, 4000, &[35.2; 8], None);
+        let second = captured(&events);
+        assert_eq!(second.len(), 1);
+        assert_eq!((second[0].capture_id, second[0].weight), (2, 35.2));
+        assert_eq!(events.last().unwrap().event_name(), "scale-captured");
+    }
+}
@@ -0,0 +1,642 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+use crate::db::{Database, PendingOperation, RfidSyncBatch, RfidSyncSummary};
+use crate::operation::Operation;
+
+const RFID_SYNC_PATH: &str = "/api/public/totem/sync/rfid-items";
+
+// Configuração do motor de sincronização. URL e chave vêm da UI (API_CONFIG)
+// e ficam gravadas no config local para os próximos starts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncConfig {
+    pub base_url: String,
+    pub api_key: String,
+    pub push_interval_secs: u64,
+    pub pull_interval_secs: u64,
+    pub batch_size: usize,
+    pub lease_secs: i64,
+    pub request_timeout_secs: u64,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        SyncConfig {
+            base_url: String::new(),
+            api_key: String::new(),
+            push_interval_secs: 30,
+            pull_interval_secs: 300,
+            batch_size: 50,
+            lease_secs: 120,
+            request_timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncState {
+    #[default]
+    Stopped,
+    Idle,
+    Pushing,
+    Pulling,
+    Error,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncStatus {
+    pub state: SyncState,
+    pub last_sync: i64,
+    pub pending_count: i64,
+    pub failed_count: i64,
+    // Resultado do último ciclo: operações movidas para a dead-letter e
+    // linhas do pull ignoradas por dados inválidos
+    pub dead_lettered: usize,
+    pub rejected_items: usize,
+    pub error: Option<String>,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PushSummary {
+    pub sent: usize,
+    pub failed: usize,
+    pub dead_lettered: usize,
+}
+
+enum SendError {
+    // Servidor inacessível: a operação volta para a fila sem contar tentativa
+    Transport(String),
+    // O servidor respondeu com erro: conta tentativa (backoff/dead-letter)
+    Rejected(String),
+    // Nem chega a ser enviada (payload não interpretável): vai direto para a
+    // dead-letter, onde pode ser corrigida e reenfileirada
+    Permanent(String),
+}
+
+pub struct SyncEngine {
+    db: Database,
+    config: SyncConfig,
+    client: reqwest::blocking::Client,
+}
+
+impl SyncEngine {
+    pub fn new(db: Database, config: SyncConfig) -> Result<Self, String> {
+        if config.base_url.trim().is_empty() {
+            return Err("URL do servidor não configurada".into());
+        }
+        let client = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(config.request_timeout_secs.max(1)))
+            .build()
+            .map_err(|e| format!("Falha ao criar cliente HTTP: {e}"))?;
+        Ok(SyncEngine { db, config, client })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.base_url.trim_end_matches('/'), path)
+    }
+
+    // Envia as operações cujo backoff venceu, na ordem da fila. Se o servidor
+    // estiver inacessível, devolve o lote inteiro e retorna erro.
+    pub fn push_due(&self) -> Result<PushSummary, String> {
+        let ops = self
+            .db
+            .claim_operations(self.config.batch_size, self.config.lease_secs)
+            .map_err(|e| format!("Erro ao reservar operações: {e}"))?;
+        let mut summary = PushSummary::default();
+
+        for op in &ops {
+            let lease_token = op.lease_token.as_deref().unwrap_or_default();
+            let db_error = |e: rusqlite::Error| format!("Erro ao atualizar operação {}: {e}", op.id);
+            match self.send_operation(op) {
+                Ok(()) => {
+                    if self.db.complete_operation(op.id, lease_token).map_err(db_error)? {
+                        summary.sent += 1;
+                    } else {
+                        // Lease venceu durante o envio: outro loop reenvia com a
+                        // mesma Idempotency-Key e o servidor descarta a repetição
+                        eprintln!("⚠️ Lease da operação {} perdido durante o envio", op.id);
+                    }
+                }
+                Err(SendError::Rejected(error)) => {
+                    let dead = self.db.fail_operation(op.id, lease_token, &error).map_err(db_error)?;
+                    summary.failed += 1;
+                    if dead {
+                        summary.dead_lettered += 1;
+                    }
+                }
+                Err(SendError::Permanent(error)) => {
+                    let dead = self.db.dead_letter_operation(op.id, lease_token, &error).map_err(db_error)?;
+                    summary.failed += 1;
+                    if dead {
+                        summary.dead_lettered += 1;
+                    }
+                }
+                Err(SendError::Transport(error)) => {
+                    let _ = self.db.release_operations(lease_token);
+                    return Err(format!("Servidor inacessível: {error}"));
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+
+    fn send_operation(&self, op: &PendingOperation) -> Result<(), SendError> {
+        // Operações enfileiradas antes da validação tipada têm versão 0
+        let operation = Operation::parse(&op.operation_type, &op.payload).map_err(|e| {
+            if op.payload_version == 0 {
+                SendError::Permanent(format!("Payload legado não interpretável: {e}"))
+            } else {
+                SendError::Permanent(e.to_string())
+            }
+        })?;
+        let (path, body) = operation_request(&operation);
+
+        let response = self
+            .client
+            .post(self.url(&path))
+            .header("x-api-key", &self.config.api_key)
+            .header("Idempotency-Key", &op.idempotency_key)
+            .json(&body)
+            .send()
+            .map_err(|e| SendError::Transport(e.to_string()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let text = response.text().unwrap_or_default();
+        Err(SendError::Rejected(format!("HTTP {}: {}", status.as_u16(), text)))
+    }
+
+    // Baixa as alterações de rfid_items a partir do cursor do servidor (ou do
+    // maior updated_at já recebido). Retorna None quando o servidor ainda não
+    // oferece o endpoint incremental.
+    pub fn pull_updates(&self) -> Result<Option<RfidSyncSummary>, String> {
+        let position = self
+            .db
+            .get_sync_cursor(Database::RFID_SYNC_ENTITY)
+            .map_err(|e| format!("Erro ao ler cursor de sincronização: {e}"))?;
+        let query = match position.cursor {
+            Some(cursor) => ("cursor", cursor),
+            None => ("since", position.high_water_mark.unwrap_or(0).to_string()),
+        };
+
+        let response = self
+            .client
+            .get(self.url(RFID_SYNC_PATH))
+            .query(&[query])
+            .header("x-api-key", &self.config.api_key)
+            .send()
+            .map_err(|e| format!("Servidor inacessível: {e}"))?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(format!("Sincronização incremental retornou HTTP {}", status.as_u16()));
+        }
+
+        let data: JsonValue = response
+            .json()
+            .map_err(|e| format!("Resposta de sincronização inválida: {e}"))?;
+        // Formato antigo: array de itens. Formato novo: { items, deleted }
+        let batch: RfidSyncBatch = if data.is_array() {
+            serde_json::from_value(serde_json::json!({ "items": data }))
+        } else {
+            serde_json::from_value(data)
+        }
+        .map_err(|e| format!("Resposta de sincronização inválida: {e}"))?;
+
+        // Mesmo um lote vazio pode trazer um cursor novo
+        let summary = self
+            .db
+            .apply_rfid_sync(&batch)
+            .map_err(|e| format!("Erro ao aplicar sincronização: {e}"))?;
+        Ok(Some(summary))
+    }
+
+    fn status(&self, state: SyncState, error: Option<String>) -> SyncStatus {
+        SyncStatus {
+            state,
+            last_sync: self.db.get_last_sync(Database::RFID_SYNC_ENTITY).unwrap_or_default(),
+            pending_count: self.db.get_pending_count().unwrap_or_default(),
+            failed_count: self.db.get_failed_count().unwrap_or_default(),
+            dead_lettered: 0,
+            rejected_items: 0,
+            error,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        }
+    }
+}
+
+// Endpoint e corpo de cada tipo de operação no servidor do totem
+fn operation_request(operation: &Operation) -> (String, JsonValue) {
+    let body = serde_json::from_str(&operation.payload_json()).unwrap_or(JsonValue::Null);
+    let path = match operation {
+        Operation::Distribute(_) => "/api/public/totem/rfid/distribute",
+        Operation::Reception(_) => "/api/public/totem/rfid/reception",
+        Operation::Retire(_) => "/api/public/totem/rfid/retire",
+        Operation::Associate(p) => {
+            if let Some(batch) = &p.batch_number {
+                let batch = batch.as_str().map(str::to_string).unwrap_or_else(|| batch.to_string());
+                let mut body = body;
+                if let Some(fields) = body.as_object_mut() {
+                    fields.remove("batchNumber");
+                }
+                return (format!("/api/public/totem/rfid/batch/{batch}/associate"), body);
+            }
+            "/api/public/totem/rfid/associate-tag"
+        }
+        Operation::ReplaceTag(_) => "/api/public/totem/rfid/replace-tag",
+        Operation::DetachTag(_) => "/api/public/totem/rfid/detach-tag",
+        Operation::Nonconformity(_) => "/api/public/totem/rfid/nonconformity",
+        Operation::Weighing(_) => "/api/public/totem/pesagens",
+    };
+    (path.to_string(), body)
+}
+
+#[derive(Default)]
+pub struct SyncController {
+    worker: Mutex<Option<SyncWorker>>,
+    status: Arc<Mutex<SyncStatus>>,
+}
+
+struct SyncWorker {
+    tx: Sender<SyncCommand>,
+    handle: JoinHandle<()>,
+    config: SyncConfig,
+}
+
+enum SyncCommand {
+    ForceSync,
+    Stop,
+}
+
+impl SyncController {
+    // (Re)inicia o loop de sincronização com a configuração informada. Com a
+    // mesma configuração o loop em andamento é mantido.
+    pub fn start(&self, app_handle: &AppHandle, db: Database, config: SyncConfig) -> Result<(), String> {
+        if let Some(worker) = self.worker.lock().unwrap().as_ref() {
+            if worker.config == config && !worker.handle.is_finished() {
+                return Ok(());
+            }
+        }
+        let engine = SyncEngine::new(db, config.clone())?;
+        self.stop();
+
+        let (tx, rx) = mpsc::channel();
+        let handle = app_handle.clone();
+        let status = Arc::clone(&self.status);
+        let worker = SyncWorker {
+            tx,
+            handle: thread::spawn(move || sync_worker(handle, engine, rx, status)),
+            config,
+        };
+        // Outro start pode ter subido um loop enquanto o anterior encerrava
+        let raced = self.worker.lock().unwrap().replace(worker);
+        shutdown(raced);
+        Ok(())
+    }
+
+    // Aguarda o loop terminar (no máximo o envio em andamento, limitado pelo
+    // timeout HTTP). O join acontece fora do lock: force_sync/status não travam.
+    pub fn stop(&self) {
+        let worker = self.worker.lock().unwrap().take();
+        shutdown(worker);
+    }
+
+    pub fn force_sync(&self) -> Result<(), String> {
+        match self.worker.lock().unwrap().as_ref() {
+            Some(worker) => worker
+                .tx
+                .send(SyncCommand::ForceSync)
+                .map_err(|_| "Sincronização encerrada".to_string()),
+            None => Err("Sincronização não iniciada".into()),
+        }
+    }
+
+    pub fn status(&self) -> SyncStatus {
+        self.status.lock().unwrap().clone()
+    }
+}
+
+fn shutdown(worker: Option<SyncWorker>) {
+    if let Some(worker) = worker {
+        let _ = worker.tx.send(SyncCommand::Stop);
+        let _ = worker.handle.join();
+    }
+}
+
+fn sync_worker(
+    app_handle: AppHandle,
+    engine: SyncEngine,
+    rx: Receiver<SyncCommand>,
+    shared: Arc<Mutex<SyncStatus>>,
+) {
+    let publish = |status: SyncStatus| {
+        *shared.lock().unwrap() = status.clone();
+        let _ = app_handle.emit_all("sync-status", status);
+    };
+    let push_every = Duration::from_secs(engine.config.push_interval_secs.max(1));
+    let pull_every = Duration::from_secs(engine.config.pull_interval_secs.max(1));
+
+    // Sincronização inicial logo após o start
+    let mut next_push = Instant::now();
+    let mut next_pull = Instant::now();
+    publish(engine.status(SyncState::Idle, None));
+
+    loop {
+        let wait = next_push.min(next_pull).saturating_duration_since(Instant::now());
+        let forced = match rx.recv_timeout(wait) {
+            Ok(SyncCommand::ForceSync) => true,
+            Err(RecvTimeoutError::Timeout) => false,
+            Ok(SyncCommand::Stop) | Err(RecvTimeoutError::Disconnected) => {
+                publish(engine.status(SyncState::Stopped, None));
+                return;
+            }
+        };
+
+        let now = Instant::now();
+        let mut error = None;
+        let (mut dead_lettered, mut rejected_items) = (0, 0);
+
+        if forced || now >= next_push {
+            publish(engine.status(SyncState::Pushing, None));
+            match engine.push_due() {
+                Ok(summary) => dead_lettered = summary.dead_lettered,
+                Err(err) => error = Some(err),
+            }
+            next_push = Instant::now() + push_every;
+        }
+
+        if forced || now >= next_pull {
+            publish(engine.status(SyncState::Pulling, error.clone()));
+            match engine.pull_updates() {
+                Ok(summary) => {
+                    rejected_items = summary.map_or(0, |summary| summary.rejected.len());
+                }
+                Err(err) => {
+                    error.get_or_insert(err);
+                }
+            }
+            next_pull = Instant::now() + pull_every;
+        }
+
+        let state = if error.is_some() { SyncState::Error } else { SyncState::Idle };
+        publish(SyncStatus {
+            dead_lettered,
+            rejected_items,
+            ..engine.status(state, error)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TempDb;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    #[derive(Debug, Clone)]
+    struct Recorded {
+        method: String,
+        path: String,
+        idempotency_key: Option<String>,
+        body: String,
+    }
+
+    // Servidor HTTP mínimo: responde por prefixo de caminho e grava as
+    // requisições. Rotas repetidas respondem em sequência; a última fica.
+    struct MockServer {
+        base_url: String,
+        requests: Arc<Mutex<Vec<Recorded>>>,
+    }
+
+    impl MockServer {
+        fn start(mut routes: Vec<(&'static str, u16, String)>) -> MockServer {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let base_url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded = Arc::clone(&requests);
+
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(mut stream) = stream else { break };
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let mut parts = line.split_whitespace();
+                    let method = parts.next().unwrap_or_default().to_string();
+                    let path = parts.next().unwrap_or_default().to_string();
+
+                    let (mut length, mut idempotency_key) = (0, None);
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        let header = header.trim_end();
+                        if header.is_empty() {
+                            break;
+                        }
+                        let (name, value) = header.split_once(':').unwrap();
+                        match name.to_ascii_lowercase().as_str() {
+                            "content-length" => length = value.trim().parse().unwrap(),
+                            "idempotency-key" => idempotency_key = Some(value.trim().to_string()),
+                            _ => {}
+                        }
+                    }
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+
+                    let matching: Vec<usize> = (0..routes.len())
+                        .filter(|&i| path.starts_with(routes[i].0))
+                        .collect();
+                    let (status, response) = match matching.as_slice() {
+                        [] => (404, String::new()),
+                        [only] => (routes[*only].1, routes[*only].2.clone()),
+                        [first, ..] => {
+                            let (_, status, body) = routes.remove(*first);
+                            (status, body)
+                        }
+                    };
+                    recorded.lock().unwrap().push(Recorded {
+                        method,
+                        path,
+                        idempotency_key,
+                        body: String::from_utf8(body).unwrap(),
+                    });
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 {status} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+                        response.len()
+                    );
+                }
+            });
+
+            MockServer { base_url, requests }
+        }
+
+        fn requests(&self) -> Vec<Recorded> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    fn engine(db: &Database, base_url: &str) -> SyncEngine {
+        let config = SyncConfig {
+            base_url: base_url.to_string(),
+            api_key: "test".into(),
+            request_timeout_secs: 5,
+            ..SyncConfig::default()
+        };
+        SyncEngine::new(db.clone(), config).unwrap()
+    }
+
+    #[test]
+    fn pushes_due_operations_to_totem_endpoints() {
+        let server = MockServer::start(vec![
+            ("/api/public/totem/rfid/reception", 201, "{}".into()),
+            ("/api/public/totem/pesagens", 500, "peso fora da faixa".into()),
+        ]);
+        let db = TempDb::new("push");
+        let reception = Operation::parse("reception", r#"{"rfidTagUid":"e2-00-aa-01"}"#).unwrap();
+        let weighing =
+            Operation::parse("weighing", r#"{"control_id":"c1","peso_total":12.5}"#).unwrap();
+        let queued = db.queue_operation(&reception).unwrap();
+        let failing = db.queue_operation(&weighing).unwrap();
+
+        let summary = engine(&db, &server.base_url).push_due().unwrap();
+        assert_eq!(summary, PushSummary { sent: 1, failed: 1, dead_lettered: 0 });
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/api/public/totem/rfid/reception");
+        assert_eq!(requests[0].idempotency_key.as_deref(), Some(queued.idempotency_key.as_str()));
+        assert_eq!(requests[0].body, r#"{"rfidTagUid":"E200AA01"}"#);
+
+        let pending = db.get_pending_operations(10).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, failing.id);
+        assert_eq!(pending[0].retry_count, 1);
+        assert!(pending[0].last_error.as_deref().unwrap().contains("HTTP 500"));
+    }
+
+    #[test]
+    fn legacy_payloads_are_sent_or_dead_lettered_on_first_attempt() {
+        let server = MockServer::start(vec![("/api/public/totem/rfid/reception", 201, "{}".into())]);
+        let db = TempDb::new("legacy-push");
+        // Operações gravadas pela webview antes da validação tipada (versão 0)
+        let legacy = |kind: &str, payload: &str| -> i64 {
+            let conn = rusqlite::Connection::open(db.path()).unwrap();
+            conn.execute(
+                "INSERT INTO pending_operations
+                 (operation_type, payload, payload_version, created_at, idempotency_key)
+                 VALUES (?1, ?2, 0, 1, ?3)",
+                rusqlite::params![kind, payload, format!("legacy-{kind}")],
+            )
+            .unwrap();
+            conn.last_insert_rowid()
+        };
+        legacy("reception", r#"{"rfidTagUid":"e2-00-aa-01"}"#);
+        let broken = legacy("distribute", r#"{"bed":"b1"}"#);
+
+        let summary = engine(&db, &server.base_url).push_due().unwrap();
+        assert_eq!(summary, PushSummary { sent: 1, failed: 1, dead_lettered: 1 });
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].body, r#"{"rfidTagUid":"E200AA01"}"#);
+
+        assert!(db.get_pending_operations(10).unwrap().is_empty());
+        let dead = db.get_failed_operation(broken).unwrap().unwrap();
+        assert_eq!(dead.operation.retry_count, 1);
+        assert!(dead.operation.last_error.unwrap().starts_with("Payload legado não interpretável"));
+    }
+
+    #[test]
+    fn unreachable_server_returns_batch_without_counting_attempts() {
+        // Porta reservada e liberada: conexão recusada
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let db = TempDb::new("offline");
+        let retire = Operation::parse("retire", r#"{"tag":"E200AA01","reason":"rasgado"}"#).unwrap();
+        db.queue_operation(&retire).unwrap();
+
+        assert!(engine(&db, &format!("http://{closed}")).push_due().is_err());
+        let due = db.claim_operations(10, 60).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].retry_count, 0);
+    }
+
+    #[test]
+    fn pulls_incremental_updates_into_cache() {
+        let server = MockServer::start(vec![(
+            RFID_SYNC_PATH,
+            200,
+            r#"{"items":[{"id":"p1","tag":"E200AA01","updated_at":100}],"deleted":[]}"#.into(),
+        )]);
+        let db = TempDb::new("pull");
+
+        let summary = engine(&db, &server.base_url).pull_updates().unwrap().unwrap();
+        assert_eq!(summary.upserted, 1);
+        assert_eq!(db.lookup_rfid_item("E200AA01").unwrap().unwrap().id, "p1");
+        assert_eq!(server.requests()[0].path, format!("{RFID_SYNC_PATH}?since=0"));
+        assert_eq!(
+            db.get_sync_cursor(Database::RFID_SYNC_ENTITY).unwrap().high_water_mark,
+            Some(100)
+        );
+
+        // Servidor sem o endpoint incremental: nada a fazer, sem erro
+        let legacy = MockServer::start(Vec::new());
+        assert!(engine(&db, &legacy.base_url).pull_updates().unwrap().is_none());
+    }
+
+    #[test]
+    fn totem_clock_ten_minutes_ahead_does_not_skip_updates() {
+        // O relógio do servidor está 10 minutos atrás do relógio do totem
+        let server_now = chrono::Utc::now().timestamp() - 600;
+        let item = |id: &str, tag: &str, updated_at: i64| {
+            format!(r#"{{"items":[{{"id":"{id}","tag":"{tag}","updated_at":{updated_at}}}]}}"#)
+        };
+        let server = MockServer::start(vec![
+            (RFID_SYNC_PATH, 200, item("p1", "E200AA01", server_now)),
+            // Alterada depois do primeiro pull, mas "antes" do relógio do totem
+            (RFID_SYNC_PATH, 200, item("p2", "E200AA02", server_now + 120)),
+            (RFID_SYNC_PATH, 200, r#"{"items":[],"cursor":"c-2"}"#.into()),
+            (RFID_SYNC_PATH, 200, r#"{"items":[]}"#.into()),
+        ]);
+        let db = TempDb::new("clock-skew");
+        let engine = engine(&db, &server.base_url);
+
+        engine.pull_updates().unwrap();
+        let local_sync = db.get_last_sync(Database::RFID_SYNC_ENTITY).unwrap();
+        assert!(local_sync > server_now + 120, "o since antigo pularia a alteração");
+
+        engine.pull_updates().unwrap();
+        assert_eq!(db.lookup_rfid_item("E200AA02").unwrap().unwrap().id, "p2");
+
+        // Quando o servidor emite um cursor, ele passa a valer
+        engine.pull_updates().unwrap();
+        engine.pull_updates().unwrap();
+
+        let paths: Vec<String> = server.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                format!("{RFID_SYNC_PATH}?since=0"),
+                format!("{RFID_SYNC_PATH}?since={server_now}"),
+                format!("{RFID_SYNC_PATH}?since={}", server_now + 120),
+                format!("{RFID_SYNC_PATH}?cursor=c-2"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use serde_json::Value as JsonValue;
+use std::fmt;
+
+// Normalização única de EPC/TID: usada em toda escrita, leitura e enfileiramento.
+// Forma canônica: hexadecimal maiúsculo, sem separadores, com número par de dígitos.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagError {
+    Empty,
+    InvalidChar { tag: String, ch: char },
+    OddLength { tag: String, len: usize },
+}
+
+impl fmt::Display for TagError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagError::Empty => write!(f, "Tag vazia"),
+            TagError::InvalidChar { tag, ch } => {
+                write!(f, "Tag '{}' contém caractere não hexadecimal '{}'", tag, ch)
+            }
+            TagError::OddLength { tag, len } => {
+                write!(f, "Tag '{}' tem número ímpar de dígitos ({})", tag, len)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TagError {}
+
+// Campos de payload de operação que carregam EPC/TID
+const PAYLOAD_TAG_FIELDS: &[&str] = &["tag", "tid", "oldTag", "newTag", "rfidTagUid"];
+const PAYLOAD_TAG_LIST_FIELDS: &[&str] = &["tags"];
+
+fn is_separator(ch: char) -> bool {
+    ch.is_whitespace() || matches!(ch, '-' | ':' | '.' | '_')
+}
+
+pub fn normalize_tag(raw: &str) -> Result<String, TagError> {
+    let mut normalized = String::with_capacity(raw.len());
+    for ch in raw.chars().filter(|ch| !is_separator(*ch)) {
+        if !ch.is_ascii_hexdigit() {
+            return Err(TagError::InvalidChar {
+                tag: raw.to_string(),
+                ch,
+            });
+        }
+        normalized.push(ch.to_ascii_uppercase());
+    }
+
+    if normalized.is_empty() {
+        return Err(TagError::Empty);
+    }
+    if !normalized.len().is_multiple_of(2) {
+        return Err(TagError::OddLength {
+            tag: raw.to_string(),
+            len: normalized.len(),
+        });
+    }
+
+    Ok(normalized)
+}
+
+pub fn normalize_optional_tag(raw: Option<&str>) -> Result<Option<String>, TagError> {
+    match raw.map(str::trim) {
+        Some("") | None => Ok(None),
+        Some(value) => normalize_tag(value).map(Some),
+    }
+}
+
+// Normaliza os campos de tag conhecidos no nível superior de um payload JSON
+pub fn normalize_payload_tags(payload: &mut JsonValue) -> Result<(), TagError> {
+    let Some(object) = payload.as_object_mut() else {
+        return Ok(());
+    };
+
+    for field in PAYLOAD_TAG_FIELDS {
+        if let Some(JsonValue::String(value)) = object.get_mut(*field) {
+            *value = normalize_tag(value)?;
+        }
+    }
+
+    for field in PAYLOAD_TAG_LIST_FIELDS {
+        if let Some(JsonValue::Array(values)) = object.get_mut(*field) {
+            for value in values.iter_mut() {
+                if let JsonValue::String(tag) = value {
+                    *tag = normalize_tag(tag)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn strips_separators_and_uppercases() {
+        assert_eq!(normalize_tag("E200ABCD").unwrap(), "E200ABCD");
+        assert_eq!(normalize_tag("e200abcd").unwrap(), "E200ABCD");
+        assert_eq!(normalize_tag(" e2 00-ab:cd.01_ff ").unwrap(), "E200ABCD01FF");
+        assert_eq!(normalize_tag("e2\t00\nab").unwrap(), "E200AB");
+    }
+
+    #[test]
+    fn rejects_empty_tags() {
+        assert_eq!(normalize_tag(""), Err(TagError::Empty));
+        assert_eq!(normalize_tag("  - :"), Err(TagError::Empty));
+        assert_eq!(normalize_optional_tag(Some("  ")), Ok(None));
+        assert_eq!(normalize_optional_tag(None), Ok(None));
+    }
+
+    #[test]
+    fn rejects_odd_length() {
+        assert_eq!(
+            normalize_tag("e2-0"),
+            Err(TagError::OddLength { tag: "e2-0".to_string(), len: 3 })
+        );
+        assert!(matches!(normalize_optional_tag(Some("ABC")), Err(TagError::OddLength { .. })));
+    }
+
+    #[test]
+    fn rejects_non_hex_characters() {
+        assert_eq!(
+            normalize_tag("E20G"),
+            Err(TagError::InvalidChar { tag: "E20G".to_string(), ch: 'G' })
+        );
+        assert!(matches!(normalize_tag("E2/00"), Err(TagError::InvalidChar { ch: '/', .. })));
+        assert!(matches!(normalize_tag("E2É0"), Err(TagError::InvalidChar { ch: 'É', .. })));
+        assert_eq!(
+            normalize_tag("E20G").unwrap_err().to_string(),
+            "Tag 'E20G' contém caractere não hexadecimal 'G'"
+        );
+    }
+
+    #[test]
+    fn normalizes_every_tag_field_of_a_payload() {
+        let mut payload = json!({
+            "tag": "e2 00",
+            "tid": "e2-80",
+            "oldTag": "aa:01",
+            "newTag": "bb.02",
+            "rfidTagUid": "cc_03",
+            "tags": ["aa bb", "CCDD"],
+            "clientId": "c1-ab",
+            "notes": "e2 00"
+        });
+        normalize_payload_tags(&mut payload).unwrap();
+        assert_eq!(
+            payload,
+            json!({
+                "tag": "E200",
+                "tid": "E280",
+                "oldTag": "AA01",
+                "newTag": "BB02",
+                "rfidTagUid": "CC03",
+                "tags": ["AABB", "CCDD"],
+                "clientId": "c1-ab",
+                "notes": "e2 00"
+            })
+        );
+    }
+
+    #[test]
+    fn payload_with_invalid_tag_is_rejected() {
+        let mut list = json!({ "tags": ["E200", "xyz"] });
+        assert!(matches!(normalize_payload_tags(&mut list), Err(TagError::InvalidChar { .. })));
+        let mut field = json!({ "rfidTagUid": "" });
+        assert_eq!(normalize_payload_tags(&mut field), Err(TagError::Empty));
+
+        // Campos ausentes, nulos ou que não são texto ficam como estão
+        let mut untouched = json!({ "tag": null, "tags": [1, "aabb"], "itemId": "x" });
+        normalize_payload_tags(&mut untouched).unwrap();
+        assert_eq!(untouched, json!({ "tag": null, "tags": [1, "AABB"], "itemId": "x" }));
+        let mut not_object = json!(["e2 00"]);
+        normalize_payload_tags(&mut not_object).unwrap();
+        assert_eq!(not_object, json!(["e2 00"]));
+    }
+}
@@ -7,6 +7,7 @@ import {
 import { Button } from '../ui/Button';
 import { useSettings } from '../../hooks/useSettings';
 import { useClients } from '../../hooks/useClients';
+import { deleteSetting } from '../../services/settingsStore';
 import { SCALE_DRIVERS, getDriverByLabel } from '../../utils/scaleDrivers';
 import { listSerialPorts, requestSerialPortPermission, listUsbDevices, requestUsbDevicePermission } from '../../utils/ports';
 import { useRFIDReader } from '../../hooks/useRFIDReader';
@@ -397,8 +398,7 @@ export const SettingsScreen: React.FC<SettingsScreenProps> = ({ onBack }) => {
             <Button 
               onClick={() => {
                 if (confirm('Resetar todas as configurações para os valores padrão?')) {
-                  localStorage.removeItem('myecolav:settings:v1');
-                  window.location.reload();
+                  deleteSetting('myecolav:settings:v1').finally(() => window.location.reload());
                 }
               }}
               variant="secondary" 
@@ -1,5 +1,6 @@
 import { useState, useEffect, useCallback } from 'react';
 import { API_CONFIG } from '../config/api';
+import { deleteSetting, getSetting, setSetting } from '../services/settingsStore';
 
 export interface Client {
   id: string;
@@ -47,26 +48,27 @@ export function useClients(): UseClientsResult {
     }
   }, []);
 
-  // Carregar cliente selecionado do localStorage
+  const [selectedLoaded, setSelectedLoaded] = useState(false);
+
+  // Carregar cliente selecionado do armazenamento local do totem
   useEffect(() => {
-    const savedClient = localStorage.getItem('selectedClient');
-    if (savedClient) {
-      try {
-        setSelectedClient(JSON.parse(savedClient));
-      } catch (err) {
-        console.error('Erro ao carregar cliente selecionado:', err);
-      }
-    }
+    getSetting<Client>('selectedClient')
+      .then((savedClient) => {
+        if (savedClient) setSelectedClient(savedClient);
+      })
+      .catch((err) => console.error('Erro ao carregar cliente selecionado:', err))
+      .finally(() => setSelectedLoaded(true));
   }, []);
 
-  // Salvar cliente selecionado no localStorage
+  // Salvar cliente selecionado
   useEffect(() => {
+    if (!selectedLoaded) return;
     if (selectedClient) {
-      localStorage.setItem('selectedClient', JSON.stringify(selectedClient));
+      setSetting('selectedClient', selectedClient);
     } else {
-      localStorage.removeItem('selectedClient');
+      deleteSetting('selectedClient');
     }
-  }, [selectedClient]);
+  }, [selectedClient, selectedLoaded]);
 
   // Carregar clientes na inicialização
   useEffect(() => {
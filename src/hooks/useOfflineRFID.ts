@@ -16,6 +16,8 @@ export interface RfidItem {
   client_id?: string;
   client_name?: string;
   updated_at: number;
+  // Status projetado por operação ainda não sincronizada
+  pending_sync?: boolean;
 }
 
 export interface LookupResult {
@@ -24,9 +26,24 @@ export interface LookupResult {
   cached: boolean;
 }
 
+// Tipos aceitos pela fila (enum Operation no Rust)
+export type OperationType =
+  | 'distribute'
+  | 'reception'
+  | 'retire'
+  | 'associate'
+  | 'replace_tag'
+  | 'detach_tag'
+  | 'nonconformity'
+  | 'weighing';
+
+// queued: nova operação; merged/dropped: coalescida com uma equivalente ainda não enviada
+export type QueueOutcome = 'queued' | 'merged' | 'dropped';
+
 export interface OfflineStats {
   rfid_items_cached: number;
   pending_operations: number;
+  dead_letter_operations: number;
   last_sync_timestamp: number;
 }
 
@@ -129,23 +146,45 @@ export function useOfflineRFID() {
   }, [isOnline, loadStats]);
 
   const queueOperation = useCallback(async (
-    operationType: string,
+    operationType: OperationType,
     payload: Record<string, any>
-  ): Promise<{ success: boolean; queued: boolean; operationId?: number }> => {
+  ): Promise<{
+    success: boolean;
+    queued: boolean;
+    operationId?: number;
+    idempotencyKey?: string;
+    outcome?: QueueOutcome;
+    error?: string;
+  }> => {
     try {
       const payloadStr = JSON.stringify(payload);
-      const operationId = await invoke<number>('queue_operation', {
+      const queued = await invoke<{
+        id: number;
+        idempotency_key: string;
+        outcome: QueueOutcome;
+      }>('queue_operation', {
         operationType,
         payload: payloadStr,
       });
       
-      console.log(`📋 Operação ${operationType} enfileirada (ID: ${operationId})`);
+      if (queued.outcome === 'queued') {
+        console.log(`📋 Operação ${operationType} enfileirada (ID: ${queued.id})`);
+      } else {
+        console.log(`📋 Operação ${operationType} ${queued.outcome === 'merged' ? 'unida à' : 'já estava na'} fila (ID: ${queued.id})`);
+      }
       await loadStats();
       
-      return { success: true, queued: true, operationId };
+      return {
+        success: true,
+        queued: true,
+        operationId: queued.id,
+        idempotencyKey: queued.idempotency_key,
+        outcome: queued.outcome,
+      };
     } catch (error) {
+      // Payload recusado pela validação do Rust: a mensagem vai direto para a UI
       console.error('❌ Erro ao enfileirar operação:', error);
-      return { success: false, queued: false };
+      return { success: false, queued: false, error: String(error) };
     }
   }, [loadStats]);
 
@@ -1,9 +1,38 @@
 import { useEffect, useRef, useState } from 'react';
+import { invoke } from '@tauri-apps/api/tauri';
+import { listen, UnlistenFn } from '@tauri-apps/api/event';
 import { apiRequest, API_CONFIG } from '../config/api';
 import { getDriverByLabel } from '../utils/scaleDrivers';
 
-// Importar comandos Tauri se disponível
-const invoke = typeof window !== 'undefined' && (window as any).__TAURI__?.invoke;
+// No app Tauri a balança RS232 é lida pelo Rust (evento scale-weight)
+const isTauri = typeof window !== 'undefined' && !!(window as any).__TAURI__;
+
+// start/stop da leitura nativa em ordem: o stop de um efeito desmontado não
+// pode chegar depois do start do efeito seguinte
+let scaleCommands: Promise<unknown> = Promise.resolve();
+function runScaleCommand<T>(command: string, args?: Record<string, unknown>): Promise<T> {
+  const next = scaleCommands.then(() => invoke<T>(command, args));
+  scaleCommands = next.catch(() => undefined);
+  return next;
+}
+
+// Payload do evento scale-weight (ScaleSnapshot no Rust)
+interface NativeScaleSnapshot {
+  weight: number;
+  stable: boolean;
+  overload: boolean;
+  connected: boolean;
+  timestamp: number;
+}
+
+// Payload do evento scale-status (ScaleStatus no Rust)
+interface NativeScaleStatus {
+  state: 'stopped' | 'connected' | 'disconnected' | 'error';
+  port: string | null;
+  protocol: string | null;
+  error: string | null;
+  timestamp: number;
+}
 
 export type ScaleConnectionMode = 'mock' | 'rs232' | 'usb' | 'tcpip';
 
@@ -84,8 +113,11 @@ export function useScaleReader(config: ScaleConfig = { mode: 'mock' }): UseScale
   const serialClose = useRef<() => Promise<void> | void>();
   const tcpSocketRef = useRef<WebSocket | null>(null);
 
-  // Detecta estabilidade: sem alteração por 1.2s
+  const nativeScale = isTauri && config.mode === 'rs232';
+
+  // Detecta estabilidade: sem alteração por 1.2s (leitura nativa já informa)
   useEffect(() => {
+    if (nativeScale) return;
     setIsStable(false);
     if (timerRef.current) window.clearTimeout(timerRef.current);
     if (weight > 0) {
@@ -94,7 +126,53 @@ export function useScaleReader(config: ScaleConfig = { mode: 'mock' }): UseScale
     return () => {
       if (timerRef.current) window.clearTimeout(timerRef.current);
     };
-  }, [weight]);
+  }, [weight, nativeScale]);
+
+  // Leitura nativa: o Rust abre a porta e publica scale-weight/scale-status (sem polling)
+  useEffect(() => {
+    if (!nativeScale) return;
+    let alive = true;
+    const unlisteners: UnlistenFn[] = [];
+    const keep = (fn: UnlistenFn) => {
+      if (alive) unlisteners.push(fn);
+      else fn();
+    };
+
+    listen<NativeScaleSnapshot>('scale-weight', event => {
+      const snapshot = event.payload;
+      setWeight(Math.max(0, snapshot.weight));
+      setIsStable(snapshot.stable);
+      setConnected(snapshot.connected);
+    }).then(keep);
+
+    listen<NativeScaleStatus>('scale-status', event => {
+      const status = event.payload;
+      setConnected(status.state === 'connected');
+      setError(status.error);
+    }).then(keep);
+
+    runScaleCommand('start_scale_reader', {
+      config: {
+        port: config.port,
+        model: config.modelLabel,
+        baudRate: config.baudRate,
+        dataBits: config.dataBits,
+        parity: config.parity,
+        stopBits: config.stopBits,
+      },
+    }).catch(err => {
+      console.error('❌ Erro ao iniciar leitura da balança:', err);
+      setConnected(false);
+      setError(String(err));
+    });
+
+    return () => {
+      alive = false;
+      unlisteners.forEach(fn => fn());
+      // Libera a porta; um novo start (troca de porta) também encerra o leitor anterior
+      runScaleCommand('stop_scale_reader').catch(() => undefined);
+    };
+  }, [nativeScale, config.port, config.modelLabel, config.baudRate, config.dataBits, config.parity, config.stopBits]);
 
   // Carregar gaiolas da API (usa BASE_URL global)
   useEffect(() => {
@@ -104,6 +182,7 @@ export function useScaleReader(config: ScaleConfig = { mode: 'mock' }): UseScale
 
   // Leitura contínua (driver ou Web Serial quando RS232 disponível)
   useEffect(() => {
+    if (nativeScale) return;
     let alive = true;
     let interval: number | null = null;
     const driver = getDriverByLabel(config.modelLabel);
@@ -289,7 +368,7 @@ export function useScaleReader(config: ScaleConfig = { mode: 'mock' }): UseScale
         tcpSocketRef.current = null;
       }
     };
-  }, [config.mode, config.modelLabel, config.port, config.vendorId, config.productId, config.baudRate, config.parity]);
+  }, [config.mode, config.modelLabel, config.port, config.vendorId, config.productId, config.baudRate, config.parity, nativeScale]);
 
   const loadCages = async () => {
     setLoading(true);
@@ -1,4 +1,5 @@
 import { useEffect, useState } from 'react';
+import { getSetting, setSetting } from '../services/settingsStore';
 
 export type ScaleMode = 'mock' | 'rs232' | 'usb' | 'tcpip';
 export interface ScaleSettings {
@@ -104,10 +105,10 @@ export function useSettings() {
   const [loaded, setLoaded] = useState(false);
 
   useEffect(() => {
-    try {
-      const raw = localStorage.getItem(STORAGE_KEY);
-      if (raw) {
-        const parsed = JSON.parse(raw);
+    let cancelled = false;
+    getSetting<Partial<Settings>>(STORAGE_KEY)
+      .then((parsed) => {
+        if (cancelled || !parsed) return;
         setSettings({
           ...DEFAULTS,
           ...parsed,
@@ -125,16 +126,19 @@ export function useSettings() {
           totem: { ...DEFAULTS.totem, ...(parsed.totem || {}) },
           network: { ...DEFAULTS.network, ...(parsed.network || {}) }
         });
-      }
-    } catch {}
-    setLoaded(true);
+      })
+      .catch(() => {})
+      .finally(() => {
+        if (!cancelled) setLoaded(true);
+      });
+    return () => {
+      cancelled = true;
+    };
   }, []);
 
   useEffect(() => {
     if (!loaded) return;
-    try {
-      localStorage.setItem(STORAGE_KEY, JSON.stringify(settings));
-    } catch {}
+    setSetting(STORAGE_KEY, settings);
   }, [loaded, settings]);
 
   return { settings, setSettings };
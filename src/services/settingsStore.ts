@@ -0,0 +1,72 @@
+import { invoke } from '@tauri-apps/api/tauri';
+
+// Chaves que antes viviam apenas no localStorage da webview
+export const LEGACY_KEYS = ['myecolav:settings:v1', 'selectedClient'];
+
+interface ConfigEntry<T> {
+  key: string;
+  value: T;
+  updated_at: number;
+}
+
+let importPromise: Promise<void> | null = null;
+
+// Valores antigos do localStorage nem sempre são JSON (ex.: selectedClient
+// gravado como texto puro): nesse caso vale o texto cru
+function parseStoredValue(raw: string): unknown {
+  try {
+    return JSON.parse(raw);
+  } catch {
+    return raw;
+  }
+}
+
+// Copia (uma única vez) os valores do localStorage para o SQLite do totem
+export function importLegacySettings(): Promise<void> {
+  if (!importPromise) {
+    importPromise = (async () => {
+      const entries: Record<string, unknown> = {};
+      for (const key of LEGACY_KEYS) {
+        const raw = localStorage.getItem(key);
+        if (!raw) continue;
+        entries[key] = parseStoredValue(raw);
+      }
+      try {
+        await invoke<number>('import_settings', { entries });
+      } catch (error) {
+        console.warn('⚠️ Não foi possível importar configurações locais:', error);
+      }
+    })();
+  }
+  return importPromise;
+}
+
+export async function getSetting<T>(key: string): Promise<T | null> {
+  await importLegacySettings();
+  try {
+    const entry = await invoke<ConfigEntry<T> | null>('get_setting', { key });
+    return entry ? entry.value : null;
+  } catch {
+    // Fora do Tauri (navegador/dev): usa o localStorage
+    const raw = localStorage.getItem(key);
+    return raw ? (parseStoredValue(raw) as T) : null;
+  }
+}
+
+export async function setSetting<T>(key: string, value: T): Promise<void> {
+  localStorage.setItem(key, JSON.stringify(value));
+  try {
+    await invoke('set_setting', { key, value });
+  } catch (error) {
+    console.warn(`⚠️ Falha ao salvar configuração ${key}:`, error);
+  }
+}
+
+export async function deleteSetting(key: string): Promise<void> {
+  localStorage.removeItem(key);
+  try {
+    await invoke('delete_setting', { key });
+  } catch (error) {
+    console.warn(`⚠️ Falha ao remover configuração ${key}:`, error);
+  }
+}